@@ -0,0 +1,536 @@
+// Annotations are extra columns whose values are computed off the UI thread.
+// Each provider is asked about every displayed entry once, and again when
+// its modification time changes; until its first answer arrives the column
+// shows a placeholder.
+
+use crate::{license, snapshot::snapshot, xattr, NodeType};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, OnceLock},
+    thread,
+    time::SystemTime,
+};
+
+pub const PLACEHOLDER: &str = "…";
+
+pub trait Provider: Send + Sync {
+    fn annotate(&self, path: &Path, node_type: NodeType) -> Option<String>;
+}
+
+pub const PROVIDER_NAMES: [&str; 9] = [
+    "loc", "xattr", "selinux", "size", "disk", "inodes", "todo", "license", "ratio",
+];
+
+pub fn provider(name: &str) -> Option<Arc<dyn Provider>> {
+    match name {
+        "loc" => Some(Arc::new(LineCount)),
+        "xattr" => Some(Arc::new(Attributes)),
+        "selinux" => Some(Arc::new(SelinuxContext)),
+        "size" => Some(Arc::new(Size { disk: false })),
+        "disk" => Some(Arc::new(Size { disk: true })),
+        "inodes" => Some(Arc::new(InodeCount)),
+        "todo" => Some(Arc::new(Markers)),
+        "license" => Some(Arc::new(LicenseHeader)),
+        "ratio" => Some(Arc::new(CompressionRatio)),
+        "binary" => Some(Arc::new(Binary)),
+        _ => None,
+    }
+}
+
+// Providers whose values filter entries instead of being shown, and which
+// are only asked about on request_value.
+const FILTERS: [&str; 1] = ["binary"];
+
+type Request = (usize, PathBuf, NodeType);
+type Response = (usize, PathBuf, Option<String>);
+
+pub struct Annotator {
+    names: Vec<String>,
+    providers: usize,
+    requests: mpsc::Sender<Request>,
+    responses: mpsc::Receiver<Response>,
+    // When each value was asked for, the entry's modification time then.
+    requested: HashMap<(usize, PathBuf), Option<SystemTime>>,
+    values: HashMap<(usize, PathBuf), Option<String>>,
+}
+
+impl Annotator {
+    pub fn new(names: &[String], workers: usize) -> Annotator {
+        let names: Vec<String> = names
+            .iter()
+            .filter(|name| provider(name).is_some())
+            .cloned()
+            .collect();
+        let providers: Vec<Arc<dyn Provider>> =
+            names.iter().filter_map(|name| provider(name)).collect();
+
+        let (request_tx, request_rx) = mpsc::channel::<Request>();
+        let (response_tx, response_rx) = mpsc::channel::<Response>();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+        let count = providers.len();
+        let providers = Arc::new(providers);
+
+        for _ in 0..workers.max(1) {
+            let request_rx = Arc::clone(&request_rx);
+            let response_tx = response_tx.clone();
+            let providers = Arc::clone(&providers);
+
+            thread::spawn(move || loop {
+                let request = request_rx.lock().unwrap().recv();
+                let (index, path, node_type) = match request {
+                    Ok(request) => request,
+                    Err(_) => break,
+                };
+
+                let value = providers[index].annotate(&path, node_type);
+                if response_tx.send((index, path, value)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Annotator {
+            names,
+            providers: count,
+            requests: request_tx,
+            responses: response_rx,
+            requested: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers == 0
+    }
+
+    // Asks for the values of an entry, except for the column `skip`. An
+    // entry that was modified since is asked about again, and its old values
+    // are shown until the new ones arrive.
+    pub fn request(&mut self, path: &Path, node_type: NodeType, skip: Option<usize>) {
+        let modified = modified(path);
+        for index in (0..self.providers).filter(|&index| Some(index) != skip) {
+            if self.shown(index) {
+                self.send(index, path, node_type, modified);
+            }
+        }
+    }
+
+    // Asks for one value of an entry, as for the filters.
+    pub fn request_value(&mut self, index: usize, path: &Path, node_type: NodeType) {
+        self.send(index, path, node_type, modified(path));
+    }
+
+    fn send(
+        &mut self,
+        index: usize,
+        path: &Path,
+        node_type: NodeType,
+        modified: Option<SystemTime>,
+    ) {
+        let key = (index, path.to_path_buf());
+        if self.requested.get(&key) != Some(&modified) {
+            self.requested.insert(key, modified);
+            let _ = self.requests.send((index, path.to_path_buf(), node_type));
+        }
+    }
+
+    fn shown(&self, index: usize) -> bool {
+        !FILTERS.contains(&self.names[index].as_str())
+    }
+
+    // Has the values of an entry asked for again on the next request, such
+    // as those of a directory something in it changed in. The old values
+    // are shown until then.
+    pub fn forget(&mut self, path: &Path) {
+        for index in 0..self.providers {
+            self.requested.remove(&(index, path.to_path_buf()));
+        }
+    }
+
+    // Collects the values computed since the last call. Returns whether
+    // anything arrived, i.e. whether the view needs to be redrawn.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((index, path, value)) = self.responses.try_recv() {
+            self.values.insert((index, path), value);
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    pub fn value(&self, index: usize, path: &Path) -> Option<&str> {
+        self.values
+            .get(&(index, path.to_path_buf()))
+            .and_then(|value| value.as_deref())
+    }
+
+    // The value of one column as it is shown, which is the placeholder
+    // until it arrives.
+    pub fn cell(&self, index: usize, path: &Path) -> Option<&str> {
+        match self.values.get(&(index, path.to_path_buf())) {
+            Some(value) => value.as_deref(),
+            None => Some(PLACEHOLDER),
+        }
+    }
+
+    // The values of all columns but `skip`.
+    pub fn column(&self, path: &Path, skip: Option<usize>) -> String {
+        let mut column = String::new();
+        for index in (0..self.providers).filter(|&index| Some(index) != skip && self.shown(index)) {
+            if let Some(value) = self.cell(index, path) {
+                column.push_str(&format!("  {}", value));
+            }
+        }
+        column
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// How much of a file is looked at to tell whether it is binary.
+const CHECKED_BYTES: usize = 8192;
+
+// Files are taken to be binary when their start holds a null byte, which
+// text in any of the usual encodings but UTF-16 never does. This is the same
+// guess git and grep make.
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents.iter().take(CHECKED_BYTES).any(|&byte| byte == 0)
+}
+
+// "binary" for binary files, which --hide-binaries leaves out. The answer is
+// kept like any other value, so each file is read again only once it
+// changes, and off the UI thread.
+pub struct Binary;
+
+impl Provider for Binary {
+    fn annotate(&self, path: &Path, node_type: NodeType) -> Option<String> {
+        if node_type != NodeType::File {
+            return None;
+        }
+
+        // Files that can't be read count as text, so they stay listed.
+        let mut start = Vec::new();
+        std::fs::File::open(path)
+            .and_then(|file| file.take(CHECKED_BYTES as u64).read_to_end(&mut start))
+            .ok()?;
+        is_binary(&start).then(|| "binary".to_string())
+    }
+}
+
+pub struct LineCount;
+
+impl Provider for LineCount {
+    fn annotate(&self, path: &Path, node_type: NodeType) -> Option<String> {
+        if node_type != NodeType::File {
+            return None;
+        }
+
+        let contents = std::fs::read(path).ok()?;
+
+        // Binary files have no meaningful line count.
+        if is_binary(&contents) {
+            return None;
+        }
+
+        let lines = contents.iter().filter(|&&byte| byte == b'\n').count();
+        Some(format!("{} lines", lines))
+    }
+}
+
+pub const MARKERS: [&str; 3] = ["TODO", "FIXME", "HACK"];
+
+// The pattern --todo searches contents for.
+pub const MARKER_PATTERN: &str = r"\b(TODO|FIXME|HACK)\b";
+
+// Counts the TODO, FIXME and HACK comments in a text file, e.g. "3 TODO,
+// 1 FIXME". Only whole words count, so a TODOS or a HACKER doesn't.
+pub struct Markers;
+
+impl Provider for Markers {
+    fn annotate(&self, path: &Path, node_type: NodeType) -> Option<String> {
+        static PATTERN: OnceLock<Regex> = OnceLock::new();
+
+        if node_type != NodeType::File {
+            return None;
+        }
+
+        let contents = std::fs::read(path).ok()?;
+        if is_binary(&contents) {
+            return None;
+        }
+
+        let pattern = PATTERN.get_or_init(|| Regex::new(MARKER_PATTERN).unwrap());
+        let text = String::from_utf8_lossy(&contents);
+        let mut counts = [0; MARKERS.len()];
+        for found in pattern.find_iter(&text) {
+            if let Some(i) = MARKERS.iter().position(|marker| *marker == found.as_str()) {
+                counts[i] += 1;
+            }
+        }
+
+        let counts: Vec<String> = MARKERS
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(marker, count)| format!("{} {}", count, marker))
+            .collect();
+        Some(counts.join(", ")).filter(|counts| !counts.is_empty())
+    }
+}
+
+// Flags the source files without a license header.
+pub struct LicenseHeader;
+
+impl Provider for LicenseHeader {
+    fn annotate(&self, path: &Path, node_type: NodeType) -> Option<String> {
+        if node_type != NodeType::File
+            || !license::is_source(path)
+            || license::has_header(path, license::header())
+        {
+            return None;
+        }
+        Some("no license header".to_string())
+    }
+}
+
+// Marks entries like ls -l does: @ when they have extended attributes and +
+// when they have an ACL.
+pub struct Attributes;
+
+impl Provider for Attributes {
+    fn annotate(&self, path: &Path, _node_type: NodeType) -> Option<String> {
+        let mut marks = String::new();
+        if !xattr::list(path).is_empty() {
+            marks.push('@');
+        }
+        if xattr::acl(path).is_some() {
+            marks.push('+');
+        }
+        Some(marks).filter(|marks| !marks.is_empty())
+    }
+}
+
+pub struct SelinuxContext;
+
+impl Provider for SelinuxContext {
+    fn annotate(&self, path: &Path, _node_type: NodeType) -> Option<String> {
+        xattr::selinux_context(path)
+    }
+}
+
+// The apparent size of an entry, or with `disk` the space it takes up on
+// disk, which is less for sparse files and more for small files rounded up
+// to whole blocks. Directories are measured with everything in them.
+pub struct Size {
+    disk: bool,
+}
+
+impl Provider for Size {
+    fn annotate(&self, path: &Path, node_type: NodeType) -> Option<String> {
+        let (apparent, disk) = usage(path)?;
+        let size = if self.disk { disk } else { apparent };
+
+        if self.disk && node_type == NodeType::File && disk < apparent {
+            return Some(format!("{} sparse", human_size(size)));
+        }
+        // Directories show how they changed since the --snapshot.
+        match snapshot().and_then(|snapshot| snapshot.delta(path, size, self.disk)) {
+            Some(delta) if node_type == NodeType::Dir => {
+                Some(format!("{} {}", human_size(size), delta))
+            }
+            _ => Some(human_size(size)),
+        }
+    }
+}
+
+// The apparent size and disk usage of a path, without following symlinks.
+fn usage(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    let mut apparent = metadata.len();
+    let mut disk = disk_usage(&metadata);
+
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).ok()?.flatten() {
+            if let Some((child_apparent, child_disk)) = usage(&entry.path()) {
+                apparent += child_apparent;
+                disk += child_disk;
+            }
+        }
+    }
+
+    Some((apparent, disk))
+}
+
+// How much of each file is sampled for the compression ratio.
+const SAMPLED_BYTES: u64 = 64 * 1024;
+
+// An estimate of how well an entry would compress, e.g. "3.2x", to tell
+// what is worth archiving. The estimate comes from the entropy of the bytes
+// at the start of each file: text uses few of the 256 byte values and
+// compresses well, while already compressed files such as images and
+// archives use all of them evenly and don't. Compressors also exploit
+// repeated strings, which this doesn't see, so real ratios are usually a
+// little better. Directories are estimated from everything in them.
+pub struct CompressionRatio;
+
+impl Provider for CompressionRatio {
+    fn annotate(&self, path: &Path, _node_type: NodeType) -> Option<String> {
+        let (size, compressed) = compressed_size(path)?;
+        if size == 0 {
+            return None;
+        }
+        // Even a file of one repeated byte needs some room compressed.
+        let ratio = size as f64 / compressed.max(size as f64 / 100.0);
+        Some(format!("{:.1}x", ratio))
+    }
+}
+
+// The size of an entry and the estimate of its compressed size.
+fn compressed_size(path: &Path) -> Option<(u64, f64)> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    if metadata.is_dir() {
+        let mut total = (0, 0.0);
+        for entry in std::fs::read_dir(path).ok()?.flatten() {
+            if let Some((size, compressed)) = compressed_size(&entry.path()) {
+                total.0 += size;
+                total.1 += compressed;
+            }
+        }
+        return Some(total);
+    }
+    if !metadata.is_file() {
+        return None;
+    }
+
+    let mut sample = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(SAMPLED_BYTES)
+        .read_to_end(&mut sample)
+        .ok()?;
+    let size = metadata.len();
+    Some((size, size as f64 * entropy(&sample) / 8.0))
+}
+
+// The entropy of the bytes in bits per byte, from 0 for one repeated byte
+// to 8 for random data.
+fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &byte in bytes {
+        counts[byte as usize] += 1;
+    }
+    let total = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// The number of inodes an entry takes up, like du --inodes: one for a file
+// and, for a directory, one for itself and each entry in it. Filesystems can
+// run out of inodes long before they run out of space when they hold
+// millions of tiny files, which this column finds. Hard links are counted
+// once.
+pub struct InodeCount;
+
+impl Provider for InodeCount {
+    fn annotate(&self, path: &Path, _node_type: NodeType) -> Option<String> {
+        let mut seen = HashSet::new();
+        Some(format!("{} inodes", inodes(path, &mut seen)?))
+    }
+}
+
+fn inodes(path: &Path, seen: &mut HashSet<(u64, u64)>) -> Option<u64> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    if !seen.insert(inode(&metadata)) {
+        return Some(0);
+    }
+
+    let mut count = 1;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).ok()?.flatten() {
+            count += inodes(&entry.path(), seen).unwrap_or(0);
+        }
+    }
+    Some(count)
+}
+
+#[cfg(unix)]
+fn inode(metadata: &std::fs::Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+// Without inode numbers every entry counts as one of its own.
+#[cfg(not(unix))]
+fn inode(_metadata: &std::fs::Metadata) -> (u64, u64) {
+    static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    (0, NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+}
+
+#[cfg(unix)]
+pub fn disk_usage(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks is always in units of 512 bytes.
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub fn disk_usage(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+const SIZE_UNITS: [char; 5] = ['K', 'M', 'G', 'T', 'P'];
+
+// A size the way ls -h shows it, in powers of 1024, e.g. 980B, 12.3K or
+// 140M.
+pub fn human_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < SIZE_UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 100.0 {
+        format!("{:.1}{}", size, SIZE_UNITS[unit])
+    } else {
+        format!("{:.0}{}", size, SIZE_UNITS[unit])
+    }
+}
+
+// The number at the start of a value, e.g. 120 for "120 lines". A unit
+// right after it scales it, so sizes compare as bytes, e.g. 12800 for
+// "12.5K".
+pub fn leading_number(value: &str) -> Option<u64> {
+    let end = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let number: f64 = value[..end].parse().ok()?;
+    let scale = match value[end..].chars().next() {
+        Some(unit) => SIZE_UNITS
+            .iter()
+            .position(|u| *u == unit)
+            .map_or(1.0, |power| 1024f64.powi(power as i32 + 1)),
+        None => 1.0,
+    };
+    Some((number * scale) as u64)
+}
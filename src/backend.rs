@@ -0,0 +1,51 @@
+// Backends resolve the types of the entries the directory listing doesn't
+// give one for, such as symlinks, which takes a stat of each. The std
+// backend makes one system call per entry. On Linux the uring backend
+// submits all of a directory's stats to an io_uring at once.
+
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StatBackend {
+    Std,
+    Uring,
+}
+
+pub const BACKEND_NAMES: [&str; 3] = ["auto", "std", "uring"];
+
+#[cfg(target_os = "linux")]
+fn uring_available() -> bool {
+    crate::uring::available()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn uring_available() -> bool {
+    false
+}
+
+// The auto backend is uring where the kernel supports it.
+pub fn backend(name: &str) -> Result<StatBackend, String> {
+    match name {
+        "std" => Ok(StatBackend::Std),
+        "uring" if uring_available() => Ok(StatBackend::Uring),
+        "uring" => {
+            Err("the uring backend needs Linux 5.6 or later with io_uring enabled".to_string())
+        }
+        _ if uring_available() => Ok(StatBackend::Uring),
+        _ => Ok(StatBackend::Std),
+    }
+}
+
+impl StatBackend {
+    // Whether each path is a directory, following symlinks.
+    pub fn are_dirs(&self, paths: &[PathBuf]) -> Vec<bool> {
+        #[cfg(target_os = "linux")]
+        if *self == StatBackend::Uring {
+            if let Some(dirs) = crate::uring::are_dirs(paths) {
+                return dirs;
+            }
+        }
+
+        paths.iter().map(|path| path.is_dir()).collect()
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    events::json_string,
+    format::{format_node, NodeInfo, TimeFormat},
+    is_timed_out, read_error,
+    util::display_path,
+    NodeType, TreeNode,
+};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_BATCH_FORMAT: &str = "{path}";
+
+// Prints one line per entry matching the pattern, without the TUI. Whether
+// an entry matches is decided by `matched` from its path and name, as globs
+// with a slash match paths; the filter fills in capture groups.
+pub fn print_batch(
+    root: &TreeNode,
+    path: &Path,
+    depth: usize,
+    filter: &Regex,
+    matched: &dyn Fn(&Path, &str) -> bool,
+    template: &str,
+    time_format: &TimeFormat,
+) {
+    if matched(path, &root.val) {
+        let info = NodeInfo {
+            path,
+            name: &root.val,
+            depth,
+            node_type: root.node_type,
+        };
+        println!("{}", format_node(template, filter, time_format, &info));
+    }
+
+    for child in &root.children {
+        print_batch(
+            child,
+            &path.join(&child.val),
+            depth + 1,
+            filter,
+            matched,
+            template,
+            time_format,
+        );
+    }
+}
+
+// The tree as one JSON object per entry, nested through `children`, e.g.
+//
+//     {"path":"src","type":"dir","matched":false,"error":null,"children":[...]}
+//
+// `matched` tells entries matching the pattern from the directories kept
+// because something in them matches.
+pub fn json_tree(root: &TreeNode, path: &Path, matched: &dyn Fn(&Path, &str) -> bool) -> String {
+    let node_type = match root.node_type {
+        NodeType::Dir => "dir",
+        NodeType::File => "file",
+    };
+    let error = if root.node_type != NodeType::Dir {
+        "null".to_string()
+    } else if is_timed_out(path) {
+        json_string("timed out")
+    } else if let Some(error) = read_error(path) {
+        json_string(&error)
+    } else {
+        "null".to_string()
+    };
+    let children: Vec<String> = root
+        .children
+        .iter()
+        .map(|child| json_tree(child, &path.join(&child.val), matched))
+        .collect();
+
+    format!(
+        "{{\"path\":{},\"type\":\"{}\",\"matched\":{},\"error\":{},\"children\":[{}]}}",
+        json_string(&display_path(path)),
+        node_type,
+        matched(path, &root.val),
+        error,
+        children.join(",")
+    )
+}
+
+// The entries of a tree for rsync --files-from or tar -T, relative to its
+// root: every file, and the directories with nothing left in them, such as
+// ones that matched when nothing in them did. A directory stands for all
+// that is in it, which tar copies by itself and rsync with -r.
+pub fn include_list(root: &TreeNode, path: &Path, list: &mut Vec<PathBuf>) {
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        if child.node_type == NodeType::File || child.children.is_empty() {
+            list.push(child_path);
+        } else {
+            include_list(child, &child_path, list);
+        }
+    }
+}
+
+// A script that recreates the directories and files of a tree, empty, in
+// the directory it is run in. The directories are made first, so the files
+// can be created in any order. Only the deepest directories need a mkdir,
+// as -p makes the ones above them.
+pub fn mkdir_script(root: &TreeNode, gitkeep: bool) -> String {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    skeleton(root, Path::new(""), gitkeep, &mut dirs, &mut files);
+
+    // A line break in the name would end the comment.
+    let name = root.val.replace(|c: char| c.is_control(), "?");
+    let mut script = format!("#!/bin/sh\n# Recreates the layout of {}.\nset -e\n", name);
+    for dir in &dirs {
+        script.push_str(&format!("mkdir -p {}\n", shell_quote(dir)));
+    }
+    for file in &files {
+        script.push_str(&format!("touch {}\n", shell_quote(file)));
+    }
+    script
+}
+
+fn skeleton(
+    root: &TreeNode,
+    path: &Path,
+    gitkeep: bool,
+    dirs: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) {
+    let has_dirs = root
+        .children
+        .iter()
+        .any(|child| child.node_type == NodeType::Dir);
+    if !has_dirs && !path.as_os_str().is_empty() {
+        dirs.push(path.to_path_buf());
+    }
+    if gitkeep && root.children.is_empty() && !path.as_os_str().is_empty() {
+        files.push(path.join(".gitkeep"));
+    }
+
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        match child.node_type {
+            NodeType::Dir => skeleton(child, &child_path, gitkeep, dirs, files),
+            NodeType::File => files.push(child_path),
+        }
+    }
+}
+
+// Quotes a path for sh. Nothing is special within single quotes, so only
+// single quotes themselves need care: each ends the quoting, adds an
+// escaped quote and starts it again. A leading dash would be taken for an
+// option, which ./ prevents.
+fn shell_quote(path: &Path) -> String {
+    let path = display_path(path);
+    let path = if path.starts_with('-') {
+        format!("./{}", path)
+    } else {
+        path
+    };
+    format!("'{}'", path.replace('\'', "'\\''"))
+}
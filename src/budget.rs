@@ -0,0 +1,55 @@
+// Size budgets for directories, set with `--budget <name>=<size>` such as
+// `target=2G`, or `*=10G` for every directory without a budget of its own.
+// Directories are measured by the disk or size column, which is added when
+// neither is shown. Those over their budget are highlighted, and Ctrl+B
+// lists them, the furthest over first.
+
+pub struct Budget {
+    // A directory name, or * for any directory.
+    name: String,
+    limit: u64,
+}
+
+impl Budget {
+    pub fn parse(rule: &str) -> Result<Budget, String> {
+        let (name, size) = rule
+            .split_once('=')
+            .ok_or(format!("{}: expected <name>=<size>", rule))?;
+        if name.is_empty() {
+            return Err(format!("{}: no directory name given", rule));
+        }
+        let limit = parse_size(size).ok_or(format!("{}: invalid size, e.g. 500M or 2G", rule))?;
+        Ok(Budget {
+            name: name.to_string(),
+            limit,
+        })
+    }
+}
+
+// A size in bytes, optionally followed by K, M, G or T for powers of 1024.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (number, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => size.split_at(i),
+        None => (size, ""),
+    };
+    let shift = match unit.trim().to_uppercase().trim_end_matches(['B', 'I']) {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+// The budget of a directory. One set for its name takes precedence over *.
+pub fn limit(budgets: &[Budget], name: &str) -> Option<u64> {
+    budgets
+        .iter()
+        .rev()
+        .find(|budget| budget.name == name)
+        .or_else(|| budgets.iter().rev().find(|budget| budget.name == "*"))
+        .map(|budget| budget.limit)
+}
@@ -0,0 +1,522 @@
+// Settings are read from `~/.config/tree-rs/config`, a file of `option =
+// value` lines named after the long command line options, e.g.
+//
+//     palette = colorblind
+//     depth-colors = true
+//     sort = size
+//
+// They are passed on as if they had been given before the command line
+// ones, which override them. Options that can be given several times, like
+// `bind`, may appear on several lines and add to the ones given on the
+// command line. Flags take `true` or `false`.
+//
+// Lines after a `[name]` header belong to a profile, which applies on top
+// of the settings before the first header when it is chosen with `--profile
+// name` or with a `profile = name` setting:
+//
+//     [media]
+//     column = size
+//     sort = size
+//     pattern = \.(mkv|mp4|flac)$
+//
+// `directory = path` sets the directory to list when none is given.
+//
+// `~/.config/tree-rs/config.toml` is read instead when it exists, with the
+// same options and profiles written as TOML. Values are then strings,
+// booleans and numbers, and options given several times take an array,
+// which may span several lines. Keys and profile names may be quoted:
+//
+//     palette = "colorblind"
+//     depth-colors = true
+//     ignore = ["target", "*.o"]
+//     bind = [
+//         "gg=top",  # like vim
+//         "G=bottom",
+//     ]
+//
+//     ["media"]
+//     pattern = '\.(mkv|mp4|flac)$'
+//
+// --config reads another file, in TOML when its name ends in .toml.
+//
+// The TUI watches the file and applies the palette, bindings and columns
+// again when it is saved.
+
+use crate::util::config_dir;
+use clap::Command;
+use std::{
+    ffi::OsString,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+    sync::OnceLock,
+};
+
+// The file given with --config.
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_config_file(path: PathBuf) {
+    let _ = CONFIG_FILE.set(path);
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = CONFIG_FILE.get() {
+        return Some(path.clone());
+    }
+    let dir = config_dir()?;
+    let toml = dir.join("config.toml");
+    if toml.exists() {
+        Some(toml)
+    } else {
+        Some(dir.join("config"))
+    }
+}
+
+// The files the settings may come from, which the TUI watches: the one
+// given with --config, or both the plain and the TOML file, as either may
+// be created while it runs.
+pub fn config_paths() -> Vec<PathBuf> {
+    if let Some(path) = CONFIG_FILE.get() {
+        return vec![path.clone()];
+    }
+    config_dir()
+        .map(|dir| vec![dir.join("config.toml"), dir.join("config")])
+        .unwrap_or_default()
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "toml")
+}
+
+// The profile given on the command line, which has to be known before the
+// command line is parsed with the settings in front of it.
+pub fn profile_arg(argv: &[OsString]) -> Option<String> {
+    early_arg(argv, "profile")
+}
+
+// The same for the config file.
+pub fn config_file_arg(argv: &[OsString]) -> Option<PathBuf> {
+    early_arg(argv, "config").map(PathBuf::from)
+}
+
+fn early_arg(argv: &[OsString], name: &str) -> Option<String> {
+    let option = format!("--{}", name);
+    let mut argv = argv.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = argv.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == option {
+            return argv.next().map(|value| value.to_string());
+        }
+        if let Some(value) = arg
+            .strip_prefix(option.as_str())
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+// The settings as command line arguments, those of the profile last. A
+// missing file has none, unless it was given with --config.
+pub fn config_args(command: &Command, profile: Option<&str>) -> Result<Vec<OsString>, String> {
+    let path = match config_path() {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && CONFIG_FILE.get().is_none() => {
+            return Ok(Vec::new())
+        }
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    parse_config(command, &text, &path, profile)
+}
+
+// The arguments of the settings in `text`, read from `path`.
+fn parse_config(
+    command: &Command,
+    text: &str,
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<Vec<OsString>, String> {
+    let toml = is_toml(path);
+
+    // The arguments of the settings before the first header and of each
+    // profile.
+    let mut base = Vec::new();
+    let mut profiles: Vec<(String, Vec<OsString>)> = Vec::new();
+    let mut default_profile = None;
+    let mut lines = text.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("{}:{}: {}", path.display(), number + 1, message);
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = match name.trim() {
+                name if toml && name.starts_with(['"', '\'']) => {
+                    toml_value(&mut name.chars().peekable()).map_err(|e| error(&e))?
+                }
+                name => name.to_string(),
+            };
+            profiles.push((name, Vec::new()));
+            continue;
+        }
+
+        let (key, mut value) = if toml {
+            toml_key(line).map_err(|e| error(&e))?
+        } else {
+            line.split_once('=')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| error("expected <option> = <value>"))?
+        };
+        let key = key.as_str();
+        // An array goes on until its closing bracket, on whichever line.
+        if toml && value.starts_with('[') {
+            while !array_closed(&value) {
+                let (_, line) = lines.next().ok_or_else(|| error("unclosed array"))?;
+                value.push('\n');
+                value.push_str(line);
+            }
+        }
+        let values = if toml {
+            toml_values(&value).map_err(|e| error(&e))?
+        } else {
+            vec![value]
+        };
+        let single = || match values.as_slice() {
+            [value] => Ok(value.as_str()),
+            _ => Err(error(&format!("{} takes one value", key))),
+        };
+        if key == "profile" {
+            if !profiles.is_empty() {
+                return Err(error("profiles can't choose a profile"));
+            }
+            default_profile = Some(single()?.to_string());
+            continue;
+        }
+        if key == "config" {
+            return Err(error("the config file can't choose a config file"));
+        }
+        let args = match profiles.last_mut() {
+            Some((_, args)) => args,
+            None => &mut base,
+        };
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key))
+            .ok_or_else(|| error(&format!("unknown option {}", key)))?;
+
+        if arg.get_action().takes_values() {
+            for value in &values {
+                args.push(OsString::from(format!("--{}", key)));
+                args.push(OsString::from(value));
+            }
+        } else {
+            match single()? {
+                "true" => args.push(OsString::from(format!("--{}", key))),
+                "false" => {}
+                _ => return Err(error(&format!("{} takes true or false", key))),
+            }
+        }
+    }
+
+    if let Some(profile) = profile.map(str::to_string).or(default_profile) {
+        let (_, args) = profiles
+            .into_iter()
+            .find(|(name, _)| *name == profile)
+            .ok_or_else(|| format!("{}: no profile named {}", path.display(), profile))?;
+        base.extend(args);
+    }
+    Ok(base)
+}
+
+// Writes the settings, replacing the file.
+pub fn write_config(settings: &[(&str, &str)]) -> std::io::Result<PathBuf> {
+    let path = config_path().ok_or(std::io::ErrorKind::NotFound)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut text = String::from("# tree-rs settings, see `tree-rs --help` for the options.\n");
+    for (key, value) in settings {
+        text.push_str(&setting(&path, key, value));
+        text.push('\n');
+    }
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+// Sets one option in the config file outside of any profile, replacing the
+// line it is on or adding one before the first profile. The rest of the file
+// is kept as it is.
+pub fn set_config(key: &str, value: &str) -> std::io::Result<PathBuf> {
+    let path = config_path().ok_or(std::io::ErrorKind::NotFound)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let setting = setting(&path, key, value);
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let profiles = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    let existing = lines[..profiles]
+        .iter()
+        .position(|line| line_key(&path, line).as_deref() == Some(key));
+    match existing {
+        Some(i) => lines[i] = setting,
+        None => lines.insert(profiles, setting),
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, lines.join("\n") + "\n")?;
+    Ok(path)
+}
+
+// The option a line sets, read the way parse_config reads it so that a
+// quoted key in a TOML file is found as well.
+fn line_key(path: &Path, line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        None
+    } else if is_toml(path) {
+        toml_key(line).ok().map(|(key, _)| key)
+    } else {
+        line.split_once('=').map(|(key, _)| key.trim().to_string())
+    }
+}
+
+// A line setting an option, with the value quoted in a TOML file unless it
+// is a boolean or a number.
+fn setting(path: &Path, key: &str, value: &str) -> String {
+    if is_toml(path) && !is_bare(value) {
+        let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("{} = \"{}\"", key, escaped)
+    } else {
+        format!("{} = {}", key, value)
+    }
+}
+
+fn is_bare(value: &str) -> bool {
+    value == "true" || value == "false" || value.parse::<f64>().is_ok()
+}
+
+// The key of a TOML line, bare or quoted, and the text of its value.
+fn toml_key(line: &str) -> Result<(String, String), String> {
+    let mut chars = line.chars().peekable();
+    let key = if matches!(chars.peek(), Some('"' | '\'')) {
+        toml_value(&mut chars)?
+    } else {
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || matches!(c, '-' | '_')) {
+            key.push(c);
+        }
+        key
+    };
+    skip_spaces(&mut chars);
+    if key.is_empty() || chars.next() != Some('=') {
+        return Err("expected <option> = <value>".to_string());
+    }
+    Ok((key, chars.collect::<String>().trim().to_string()))
+}
+
+// Whether the brackets of an array are balanced, leaving out those in
+// strings and comments.
+fn array_closed(value: &str) -> bool {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0
+}
+
+// The values of a TOML setting, several for an array. A comment may follow,
+// and arrays may have comments inside.
+fn toml_values(value: &str) -> Result<Vec<String>, String> {
+    let mut chars = value.chars().peekable();
+    let values = if chars.next_if_eq(&'[').is_some() {
+        let mut values = Vec::new();
+        loop {
+            skip_blank(&mut chars);
+            if chars.next_if_eq(&']').is_some() {
+                break;
+            }
+            values.push(toml_value(&mut chars)?);
+            skip_blank(&mut chars);
+            match chars.next() {
+                Some(',') => {}
+                Some(']') => break,
+                _ => return Err("expected , or ] in the array".to_string()),
+            }
+        }
+        values
+    } else {
+        vec![toml_value(&mut chars)?]
+    };
+
+    skip_spaces(&mut chars);
+    match chars.next() {
+        None | Some('#') => Ok(values),
+        Some(_) => Err("unexpected text after the value".to_string()),
+    }
+}
+
+// A string in double quotes with escapes, one in single quotes taken as it
+// is, a boolean or a number.
+fn toml_value(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut value = String::new();
+    if chars.next_if_eq(&'"').is_some() {
+        loop {
+            match chars.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => value.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    _ => return Err("unsupported escape in the string".to_string()),
+                }),
+                Some(c) => value.push(c),
+                None => return Err("unclosed string".to_string()),
+            }
+        }
+    }
+    if chars.next_if_eq(&'\'').is_some() {
+        loop {
+            match chars.next() {
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+                None => return Err("unclosed string".to_string()),
+            }
+        }
+    }
+
+    while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | '#')) {
+        value.push(c);
+    }
+    if is_bare(&value) {
+        Ok(value)
+    } else {
+        Err("expected a quoted string, a boolean or a number".to_string())
+    }
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+// Skips spaces, line breaks and comments between the values of an array.
+fn skip_blank(chars: &mut Peekable<Chars>) {
+    skip_spaces(chars);
+    while chars.next_if_eq(&'#').is_some() {
+        while chars.next_if(|&c| c != '\n').is_some() {}
+        skip_spaces(chars);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{arg, ArgAction};
+
+    fn command() -> Command {
+        Command::new("tree-rs")
+            .arg(arg!(--palette <name>))
+            .arg(arg!(--bind <chord>).action(ArgAction::Append))
+            .arg(arg!(--hidden))
+            .arg(arg!(--directory <dirname>))
+    }
+
+    fn parse(text: &str, profile: Option<&str>) -> Result<Vec<String>, String> {
+        let args = parse_config(&command(), text, Path::new("config.toml"), profile)?;
+        Ok(args
+            .into_iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    #[test]
+    fn reads_values() {
+        let args = parse("palette = \"colorblind\"\nhidden = true # comment\n", None);
+        assert_eq!(args.unwrap(), ["--palette", "colorblind", "--hidden"]);
+    }
+
+    #[test]
+    fn reads_quoted_keys() {
+        let args = parse("\"palette\" = 'dark'\n'directory' = \"/tmp\"\n", None);
+        assert_eq!(args.unwrap(), ["--palette", "dark", "--directory", "/tmp"]);
+    }
+
+    #[test]
+    fn reads_arrays_over_several_lines() {
+        let text = "bind = [\n  \"gg=top\", # like vim\n  \"]=bottom\",\n]\nhidden = false\n";
+        assert_eq!(
+            parse(text, None).unwrap(),
+            ["--bind", "gg=top", "--bind", "]=bottom"]
+        );
+    }
+
+    #[test]
+    fn reports_unclosed_arrays() {
+        let error = parse("bind = [\"gg=top\",\n", None).unwrap_err();
+        assert_eq!(error, "config.toml:1: unclosed array");
+    }
+
+    #[test]
+    fn reads_quoted_profiles() {
+        let text = "palette = \"dark\"\n[\"media files\"]\npalette = \"light\"\n";
+        assert_eq!(
+            parse(text, Some("media files")).unwrap(),
+            ["--palette", "dark", "--palette", "light"]
+        );
+    }
+
+    #[test]
+    fn finds_the_key_of_a_line() {
+        let toml = Path::new("config.toml");
+        assert_eq!(line_key(toml, "\"palette\" = 'dark'").unwrap(), "palette");
+        assert_eq!(line_key(toml, "  'hidden'=true").unwrap(), "hidden");
+        assert_eq!(line_key(toml, "# palette = \"dark\""), None);
+        assert_eq!(
+            line_key(Path::new("config"), "palette = dark").unwrap(),
+            "palette"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_options() {
+        let error = parse("colour = \"red\"\n", None).unwrap_err();
+        assert_eq!(error, "config.toml:1: unknown option colour");
+    }
+}
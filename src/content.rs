@@ -0,0 +1,192 @@
+// In content mode the pattern is matched against what is in files instead of
+// their names, like a tree-shaped grep. Only the start of each file is
+// searched, and binary files are skipped. The TUI searches in the background
+// and lists files as their matches come in; each search belongs to one
+// pattern, and answers for an earlier pattern are dropped.
+
+use crate::{NodeType, TreeNode};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+// How much of each file is searched.
+const SEARCHED_BYTES: u64 = 64 * 1024;
+
+// The longest line shown next to a file.
+const SHOWN_CHARS: usize = 60;
+
+// The first line of a file the pattern matches, with its number counted
+// from 1.
+pub struct ContentMatch {
+    pub number: usize,
+    pub line: String,
+}
+
+impl ContentMatch {
+    pub fn display(&self) -> String {
+        let mut line: String = self.line.trim().chars().take(SHOWN_CHARS).collect();
+        if self.line.trim().chars().count() > SHOWN_CHARS {
+            line.push('…');
+        }
+        format!("{}: {}", self.number, line)
+    }
+}
+
+pub fn first_match(path: &Path, filter: &Regex) -> Option<ContentMatch> {
+    let mut contents = Vec::new();
+    std::fs::File::open(path)
+        .ok()?
+        .take(SEARCHED_BYTES)
+        .read_to_end(&mut contents)
+        .ok()?;
+    if contents.contains(&0) {
+        return None;
+    }
+
+    String::from_utf8_lossy(&contents)
+        .lines()
+        .enumerate()
+        .find(|(_, line)| filter.is_match(line))
+        .map(|(i, line)| ContentMatch {
+            number: i + 1,
+            line: line.to_string(),
+        })
+}
+
+type Request = (usize, Arc<Regex>, PathBuf);
+type Response = (usize, PathBuf, Option<ContentMatch>);
+
+pub struct ContentSearch {
+    // Counts the patterns searched for, so answers can be told apart and
+    // files still queued for an earlier pattern are skipped.
+    generation: Arc<AtomicUsize>,
+    pattern: String,
+    filter: Arc<Regex>,
+    requests: mpsc::Sender<Request>,
+    responses: mpsc::Receiver<Response>,
+    requested: HashSet<PathBuf>,
+    matches: HashMap<PathBuf, Option<ContentMatch>>,
+}
+
+impl ContentSearch {
+    pub fn new(workers: usize) -> ContentSearch {
+        let (request_tx, request_rx) = mpsc::channel::<Request>();
+        let (response_tx, response_rx) = mpsc::channel::<Response>();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+        let current = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..workers.max(1) {
+            let request_rx = Arc::clone(&request_rx);
+            let response_tx = response_tx.clone();
+            let current = Arc::clone(&current);
+
+            thread::spawn(move || loop {
+                let request = request_rx.lock().unwrap().recv();
+                let (generation, filter, path) = match request {
+                    Ok(request) => request,
+                    Err(_) => break,
+                };
+                if generation != current.load(Ordering::Relaxed) {
+                    continue;
+                }
+
+                let found = first_match(&path, &filter);
+                if response_tx.send((generation, path, found)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        ContentSearch {
+            generation: current,
+            pattern: String::new(),
+            filter: Arc::new(Regex::new("").unwrap()),
+            requests: request_tx,
+            responses: response_rx,
+            requested: HashSet::new(),
+            matches: HashMap::new(),
+        }
+    }
+
+    // Starts over when the pattern changed.
+    pub fn set_pattern(&mut self, pattern: &str, filter: &Regex) {
+        if pattern == self.pattern {
+            return;
+        }
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.pattern = pattern.to_string();
+        self.filter = Arc::new(filter.clone());
+        self.requested.clear();
+        self.matches.clear();
+    }
+
+    // Asks for the files of a tree that haven't been searched yet.
+    pub fn request(&mut self, root: &TreeNode, path: &Path) {
+        for child in &root.children {
+            let child_path = path.join(&child.val);
+            if child.node_type == NodeType::File && !self.requested.contains(&child_path) {
+                self.requested.insert(child_path.clone());
+                let _ = self.requests.send((
+                    self.generation.load(Ordering::Relaxed),
+                    Arc::clone(&self.filter),
+                    child_path.clone(),
+                ));
+            }
+            self.request(child, &child_path);
+        }
+    }
+
+    // Collects the answers since the last call. Returns whether anything
+    // arrived.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((generation, path, found)) = self.responses.try_recv() {
+            if generation == self.generation.load(Ordering::Relaxed) {
+                self.matches.insert(path, found);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&ContentMatch> {
+        self.matches.get(path).and_then(|found| found.as_ref())
+    }
+
+    // The files found so far.
+    pub fn count(&self) -> usize {
+        self.matches
+            .values()
+            .filter(|found| found.is_some())
+            .count()
+    }
+}
+
+// Returns a copy of the tree with only the files whose contents `matched`
+// and the directories leading to them.
+pub fn content_tree(root: &TreeNode, path: &Path, matched: &dyn Fn(&Path) -> bool) -> TreeNode {
+    let mut new_root = TreeNode {
+        color: root.color,
+        val: root.val.clone(),
+        children: Vec::new(),
+        node_type: root.node_type,
+    };
+
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        let node = content_tree(child, &child_path, matched);
+        if !node.children.is_empty() || child.node_type == NodeType::File && matched(&child_path) {
+            new_root.children.push(node);
+        }
+    }
+
+    new_root
+}
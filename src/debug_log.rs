@@ -0,0 +1,17 @@
+// With --debug-log, scan progress, keys and render timings are logged to a
+// file, so slow scans and keys a terminal sends oddly can be diagnosed from
+// a user's report. Without it nothing subscribes and the events cost next
+// to nothing.
+
+use std::{fs::File, path::Path, sync::Mutex};
+use tracing::Level;
+
+pub fn init(path: &Path) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(Level::DEBUG)
+        .init();
+    Ok(())
+}
@@ -0,0 +1,63 @@
+// The details pane shows metadata of the selected entry that is too long
+// for a column, one labelled line per item.
+
+use crate::{
+    format::{mtime, perms, TimeFormat},
+    i18n::tr,
+    util::display_path,
+    xattr,
+};
+use std::path::Path;
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+pub fn details(path: &Path, time_format: &TimeFormat) -> Vec<Spans<'static>> {
+    let mut items = vec![(tr("details_path"), display_path(path))];
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        let kind = if metadata.is_dir() {
+            tr("directory")
+        } else {
+            tr("file")
+        };
+        items.push((tr("details_type"), kind));
+        items.push((tr("details_size"), metadata.len().to_string()));
+        if let Some(modified) = mtime(&metadata, time_format) {
+            items.push((tr("details_modified"), modified));
+        }
+        items.push((tr("details_permissions"), perms(&metadata)));
+    }
+
+    let attributes = xattr::list(path);
+    items.push((
+        tr("details_xattrs"),
+        if attributes.is_empty() {
+            tr("details_none")
+        } else {
+            attributes.join(", ")
+        },
+    ));
+
+    if let Some(acl) = xattr::acl(path) {
+        items.push((tr("details_acl"), acl.join(", ")));
+    }
+
+    if let Some(context) = xattr::selinux_context(path) {
+        items.push((tr("details_selinux"), context));
+    }
+
+    items
+        .into_iter()
+        .map(|(label, value)| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{}: ", label),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(value),
+            ])
+        })
+        .collect()
+}
@@ -0,0 +1,69 @@
+use crate::util::display_path;
+use std::io::{self, Write};
+use std::path::Path;
+
+pub enum Value<'a> {
+    Str(&'a str),
+    Num(usize),
+    Bool(bool),
+}
+
+// Events are written to stderr as JSON lines, one object per event, so they
+// can be consumed by a wrapping program without interfering with the TUI.
+pub fn emit_event(event: &str, fields: &[(&str, Value)]) {
+    let mut line = format!("{{\"event\":{}", json_string(event));
+    for (key, value) in fields {
+        let value = match value {
+            Value::Str(s) => json_string(s),
+            Value::Num(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+        };
+        line.push_str(&format!(",{}:{}", json_string(key), value));
+    }
+    line.push('}');
+
+    let mut stderr = io::stderr();
+    writeln!(stderr, "{}", line).unwrap();
+    stderr.flush().unwrap();
+}
+
+pub fn emit_scan(entries: usize, done: bool) {
+    emit_event(
+        "scan",
+        &[
+            ("entries", Value::Num(entries)),
+            ("done", Value::Bool(done)),
+        ],
+    );
+}
+
+pub fn emit_matches(pattern: &str, count: usize) {
+    emit_event(
+        "matches",
+        &[
+            ("pattern", Value::Str(pattern)),
+            ("count", Value::Num(count)),
+        ],
+    );
+}
+
+pub fn emit_selection(path: &Path) {
+    emit_event("selection", &[("path", Value::Str(&display_path(path)))]);
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -0,0 +1,193 @@
+// Files can be deleted, renamed and created without leaving the TUI. Each
+// operation asks first: deleting waits for y, and renaming and creating
+// take a name typed into a prompt. The tree in memory is then changed to
+// match, so the directory doesn't have to be scanned again.
+//
+// Names are taken as they are typed and have to stay in their directory, so
+// a name with a slash is refused. When creating, a trailing slash makes a
+// directory instead of a file.
+
+use crate::{
+    i18n::{tr, trf},
+    type_color,
+    util::{display_path, node_name},
+    NodeType, TreeNode,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+pub enum Prompt {
+    Delete(PathBuf),
+    // The entry and the new name typed so far, which starts as the old one.
+    Rename(PathBuf, String),
+    // The directory to create in and the name typed so far.
+    Create(PathBuf, String),
+}
+
+impl Prompt {
+    pub fn title(&self) -> String {
+        match self {
+            Prompt::Delete(_) => tr("delete"),
+            Prompt::Rename(path, _) => trf("rename", &[&node_name(path)]),
+            Prompt::Create(dir, _) => trf("create", &[&display_path(dir)]),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self {
+            Prompt::Delete(path) if is_dir(path) => trf("confirm_delete_dir", &[&node_name(path)]),
+            Prompt::Delete(path) => trf("confirm_delete", &[&node_name(path)]),
+            Prompt::Rename(_, name) | Prompt::Create(_, name) => name.clone(),
+        }
+    }
+
+    // The name being typed, if the prompt takes one.
+    pub fn input(&mut self) -> Option<&mut String> {
+        match self {
+            Prompt::Delete(_) => None,
+            Prompt::Rename(_, name) | Prompt::Create(_, name) => Some(name),
+        }
+    }
+
+    // Carries out the operation on disk.
+    pub fn run(&self) -> io::Result<Change> {
+        match self {
+            Prompt::Delete(path) => {
+                if is_dir(path) {
+                    std::fs::remove_dir_all(path)?;
+                } else {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Change::Removed(path.clone()))
+            }
+            Prompt::Rename(path, name) => {
+                let to = path.with_file_name(checked_name(name)?);
+                // Renaming replaces an existing file without asking.
+                if std::fs::symlink_metadata(&to).is_ok() {
+                    return Err(io::ErrorKind::AlreadyExists.into());
+                }
+                std::fs::rename(path, &to)?;
+                Ok(Change::Renamed(path.clone(), to))
+            }
+            Prompt::Create(dir, name) => match name.strip_suffix('/') {
+                Some(name) => {
+                    let path = dir.join(checked_name(name)?);
+                    std::fs::create_dir(&path)?;
+                    Ok(Change::Created(path, NodeType::Dir))
+                }
+                None => {
+                    let path = dir.join(checked_name(name)?);
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)?;
+                    Ok(Change::Created(path, NodeType::File))
+                }
+            },
+        }
+    }
+}
+
+// Symlinks to directories are removed as links.
+fn is_dir(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+}
+
+fn checked_name(name: &str) -> io::Result<&str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            trf("invalid_name", &[&name]),
+        ));
+    }
+    Ok(name)
+}
+
+pub enum Change {
+    Removed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+    Created(PathBuf, NodeType),
+}
+
+impl Change {
+    pub fn describe(&self) -> String {
+        match self {
+            Change::Removed(path) => trf("deleted", &[&node_name(path)]),
+            Change::Renamed(from, to) => trf("renamed", &[&node_name(from), &node_name(to)]),
+            Change::Created(path, _) => trf("created", &[&node_name(path)]),
+        }
+    }
+
+    // The entry to select afterwards.
+    pub fn selected(&self) -> Option<PathBuf> {
+        match self {
+            Change::Removed(_) => None,
+            Change::Renamed(_, path) | Change::Created(path, _) => Some(path.clone()),
+        }
+    }
+}
+
+// Changes the tree the way the operation changed the disk. Entries stay in
+// the order a scan would list them in.
+pub fn update_tree(root: &mut TreeNode, dirname: &Path, change: &Change) {
+    match change {
+        Change::Removed(path) => {
+            if let Some((parent, name)) = parent_node(root, dirname, path) {
+                parent.children.retain(|child| child.val != name);
+            }
+        }
+        Change::Renamed(from, to) => {
+            if let Some((parent, name)) = parent_node(root, dirname, from) {
+                if let Some(index) = parent.children.iter().position(|child| child.val == name) {
+                    let mut node = parent.children.remove(index);
+                    node.val = node_name(to);
+                    insert_sorted(parent, node);
+                }
+            }
+        }
+        // Entries created here are reported again by --watch.
+        Change::Created(path, node_type) => {
+            if let Some((parent, name)) = parent_node(root, dirname, path) {
+                if parent.children.iter().any(|child| child.val == name) {
+                    return;
+                }
+                insert_sorted(
+                    parent,
+                    TreeNode {
+                        color: type_color(*node_type),
+                        val: node_name(path),
+                        children: Vec::new(),
+                        node_type: *node_type,
+                    },
+                );
+            }
+        }
+    }
+}
+
+// The node of the directory a path is in, and the path's name.
+fn parent_node<'a>(
+    root: &'a mut TreeNode,
+    dirname: &Path,
+    path: &Path,
+) -> Option<(&'a mut TreeNode, String)> {
+    let relative = path.strip_prefix(dirname).ok()?;
+    let name = relative.file_name()?.to_string_lossy().to_string();
+    let mut node = root;
+    for component in relative.parent()?.iter() {
+        let val = component.to_string_lossy();
+        node = node.children.iter_mut().find(|child| child.val == val)?;
+    }
+    Some((node, name))
+}
+
+fn insert_sorted(parent: &mut TreeNode, node: TreeNode) {
+    let index = parent
+        .children
+        .iter()
+        .position(|child| child.val > node.val)
+        .unwrap_or(parent.children.len());
+    parent.children.insert(index, node);
+}
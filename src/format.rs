@@ -0,0 +1,178 @@
+use crate::{
+    util::{display_path, fnv1a, FNV_OFFSET},
+    xattr::quarantine,
+    NodeType,
+};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, SecondsFormat,
+};
+use regex::Regex;
+use std::{
+    fs::{File, Metadata},
+    io::{BufRead, BufReader},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+pub const FORMAT_HELP: &str = "Format each entry with the template. Capture groups of the \
+pattern are expanded from $1 or ${name}, and the placeholders {path}, {name}, {depth}, \
+{type}, {size}, {mtime}, {perms}, {hash} and {quarantine} are replaced with the entry's values. \
+{mtime} is formatted as set by --time-format and {hash} is a 64-bit FNV-1a hash of the file's \
+contents. {quarantine} is the application that downloaded the file on macOS, or - if it \
+isn't quarantined. The escapes \\t and \\n insert a tab and a newline.";
+
+pub const TIME_FORMAT_HELP: &str = "Format of {mtime}: epoch for seconds since the epoch \
+(the default), iso for ISO 8601 with the local UTC offset, or a strftime pattern such as \
+%Y-%m-%d %H:%M, which is in the local timezone.";
+
+pub enum TimeFormat {
+    Epoch,
+    Iso,
+    Strftime(String),
+}
+
+impl TimeFormat {
+    pub fn parse(format: &str) -> Result<TimeFormat, String> {
+        match format {
+            "epoch" => Ok(TimeFormat::Epoch),
+            "iso" => Ok(TimeFormat::Iso),
+            _ if StrftimeItems::new(format).any(|item| item == Item::Error) => {
+                Err(format!("invalid strftime pattern: {}", format))
+            }
+            _ => Ok(TimeFormat::Strftime(format.to_string())),
+        }
+    }
+}
+
+pub struct NodeInfo<'a> {
+    pub path: &'a Path,
+    pub name: &'a str,
+    pub depth: usize,
+    pub node_type: NodeType,
+}
+
+pub fn format_node(
+    template: &str,
+    filter: &Regex,
+    time_format: &TimeFormat,
+    info: &NodeInfo,
+) -> String {
+    let template = match filter.captures(info.name) {
+        Some(captures) => {
+            let mut expanded = String::new();
+            captures.expand(template, &mut expanded);
+            expanded
+        }
+        None => template.to_string(),
+    };
+    let template = template.replace("\\t", "\t").replace("\\n", "\n");
+
+    // Metadata is only read when the template asks for it.
+    let mut cached: Option<Option<Metadata>> = None;
+    let mut metadata = || {
+        cached
+            .get_or_insert_with(|| std::fs::symlink_metadata(info.path).ok())
+            .clone()
+    };
+
+    let mut formatted = String::new();
+    let mut rest = template.as_str();
+
+    while let Some(start) = rest.find('{') {
+        formatted.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+
+        match &rest[1..end] {
+            "path" => formatted.push_str(&display_path(info.path)),
+            "name" => formatted.push_str(info.name),
+            "depth" => formatted.push_str(&info.depth.to_string()),
+            "type" => formatted.push_str(match info.node_type {
+                NodeType::File => "file",
+                NodeType::Dir => "dir",
+            }),
+            "size" => {
+                formatted.push_str(&metadata().map_or("?".to_string(), |m| m.len().to_string()))
+            }
+            "mtime" => formatted.push_str(
+                &metadata()
+                    .and_then(|m| mtime(&m, time_format))
+                    .unwrap_or("?".to_string()),
+            ),
+            "perms" => formatted.push_str(&metadata().map_or("?".to_string(), |m| perms(&m))),
+            "hash" => formatted.push_str(&hash(info.path).unwrap_or("-".to_string())),
+            "quarantine" => formatted.push_str(&quarantine(info.path).unwrap_or("-".to_string())),
+            _ => formatted.push_str(&rest[..=end]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    formatted.push_str(rest);
+    formatted
+}
+
+pub fn mtime(metadata: &Metadata, time_format: &TimeFormat) -> Option<String> {
+    let modified = metadata.modified().ok()?;
+    let local = DateTime::<Local>::from(modified);
+
+    Some(match time_format {
+        TimeFormat::Epoch => modified
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_secs()
+            .to_string(),
+        TimeFormat::Iso => local.to_rfc3339_opts(SecondsFormat::Secs, false),
+        TimeFormat::Strftime(format) => local.format(format).to_string(),
+    })
+}
+
+#[cfg(unix)]
+pub fn perms(metadata: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    let mut perms = String::new();
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        perms.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        perms.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        perms.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    perms
+}
+
+#[cfg(not(unix))]
+pub fn perms(metadata: &Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "r-".to_string()
+    } else {
+        "rw".to_string()
+    }
+}
+
+// Directories and unreadable files have no hash.
+fn hash(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+
+    // Files are read a buffer at a time, as they can be larger than memory.
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut hash = FNV_OFFSET;
+    loop {
+        let chunk = reader.fill_buf().ok()?;
+        if chunk.is_empty() {
+            break;
+        }
+        hash = fnv1a(hash, chunk);
+        let length = chunk.len();
+        reader.consume(length);
+    }
+    Some(format!("{:016x}", hash))
+}
@@ -0,0 +1,112 @@
+// With --git, entries inside a git repository are marked with their status,
+// taken from `git status --porcelain` when the TUI starts. Directories are
+// marked when something in them changed. Untracked and ignored directories
+// are reported by git as a whole, so everything in them is marked the same.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum GitStatus {
+    Ignored,
+    Untracked,
+    // Changes are staged and none are left in the working tree.
+    Staged,
+    Modified,
+}
+
+impl GitStatus {
+    pub fn marker(&self) -> char {
+        match self {
+            GitStatus::Ignored => '!',
+            GitStatus::Untracked => '?',
+            GitStatus::Staged => '+',
+            GitStatus::Modified => 'M',
+        }
+    }
+}
+
+pub struct GitStatuses {
+    // The entries git reported.
+    statuses: HashMap<PathBuf, GitStatus>,
+    // The directories leading to them.
+    dirs: HashMap<PathBuf, GitStatus>,
+}
+
+impl GitStatuses {
+    // None if the directory isn't in a repository or git can't be run.
+    pub fn load(dir: &Path) -> Option<GitStatuses> {
+        let toplevel = git(dir, &["rev-parse", "--show-toplevel"])?;
+        let toplevel = PathBuf::from(toplevel.trim_end()).canonicalize().ok()?;
+        let output = git(dir, &["status", "--porcelain", "-z", "--ignored", "."])?;
+
+        let mut statuses = HashMap::new();
+        let mut dirs = HashMap::new();
+        let mut records = output.split('\0');
+        while let Some(record) = records.next() {
+            if record.len() < 4 {
+                continue;
+            }
+            let (code, path) = record.split_at(3);
+            let mut code = code.chars();
+            let (x, y) = (code.next().unwrap_or(' '), code.next().unwrap_or(' '));
+
+            // Renames and copies are followed by the path they came from.
+            if matches!(x, 'R' | 'C') {
+                records.next();
+            }
+
+            let status = match (x, y) {
+                ('!', _) => GitStatus::Ignored,
+                ('?', _) => GitStatus::Untracked,
+                (_, ' ') => GitStatus::Staged,
+                _ => GitStatus::Modified,
+            };
+            let path = toplevel.join(path.trim_end_matches('/'));
+
+            // The directories leading to a change are marked with the most
+            // pressing status in them. Ignored entries don't count.
+            if status != GitStatus::Ignored {
+                for ancestor in path.ancestors().skip(1) {
+                    if !ancestor.starts_with(&toplevel) {
+                        break;
+                    }
+                    let marked = dirs.entry(ancestor.to_path_buf()).or_insert(status);
+                    if status > *marked {
+                        *marked = status;
+                    }
+                }
+            }
+            statuses.insert(path, status);
+        }
+
+        Some(GitStatuses { statuses, dirs })
+    }
+
+    pub fn get(&self, path: &Path) -> Option<GitStatus> {
+        if let Some(status) = self.statuses.get(path).or_else(|| self.dirs.get(path)) {
+            return Some(*status);
+        }
+        path.ancestors()
+            .skip(1)
+            .filter_map(|ancestor| self.statuses.get(ancestor))
+            .find(|status| matches!(status, GitStatus::Untracked | GitStatus::Ignored))
+            .copied()
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
@@ -0,0 +1,84 @@
+// UI strings are looked up by key so that they can be translated. The
+// language is taken from LC_ALL, LC_MESSAGES or LANG. Translations are files
+// of `key = text` lines in which `{}` stands for the arguments in order. The
+// ones in locale/ are built in, and `~/.config/tree-rs/locale/<language>.txt`
+// adds to or overrides them, so a translation can be tried out before it is
+// contributed. Keys missing from a translation fall back to English.
+
+use crate::util::config_dir;
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+const ENGLISH: &str = include_str!("../locale/en.txt");
+
+const TRANSLATIONS: [(&str, &str); 1] = [("de", include_str!("../locale/de.txt"))];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+fn parse(text: &str, catalog: &mut HashMap<String, String>) {
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            catalog.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+}
+
+// The language part of a locale such as "de_DE.UTF-8".
+fn language() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())?;
+    let language = locale.split(['_', '.', '@']).next()?.to_lowercase();
+
+    match language.as_str() {
+        "" | "c" | "posix" => None,
+        _ => Some(language),
+    }
+}
+
+fn load() -> HashMap<String, String> {
+    let mut catalog = HashMap::new();
+    parse(ENGLISH, &mut catalog);
+
+    if let Some(language) = language() {
+        if let Some((_, text)) = TRANSLATIONS.iter().find(|(name, _)| **name == language) {
+            parse(text, &mut catalog);
+        }
+
+        let path = config_dir().map(|dir| dir.join("locale").join(format!("{}.txt", language)));
+        if let Some(Ok(text)) = path.map(std::fs::read_to_string) {
+            parse(&text, &mut catalog);
+        }
+    }
+
+    catalog
+}
+
+pub fn tr(key: &str) -> String {
+    trf(key, &[])
+}
+
+pub fn trf(key: &str, args: &[&dyn Display]) -> String {
+    let catalog = CATALOG.get_or_init(load);
+    let text = match catalog.get(key) {
+        Some(text) => text.as_str(),
+        None => key,
+    };
+
+    let mut args = args.iter();
+    let mut translated = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{}") {
+        translated.push_str(&rest[..start]);
+        if let Some(arg) = args.next() {
+            translated.push_str(&arg.to_string());
+        }
+        rest = &rest[start + 2..];
+    }
+    translated.push_str(rest);
+    translated
+}
@@ -0,0 +1,116 @@
+// With --icons every entry is preceded by a Nerd Font glyph for its kind,
+// picked by its name, then its extension, then whether it is a directory.
+// The glyphs are in the private use area, so they show up as boxes or
+// question marks without a patched font, which is why icons are off unless
+// asked for.
+
+use crate::NodeType;
+use std::path::Path;
+
+const DIR: char = '\u{f07b}';
+const FILE: char = '\u{f15b}';
+const SYMLINK: char = '\u{f481}';
+const SYMLINK_DIR: char = '\u{f482}';
+
+// Entries known by their whole name, compared ignoring case.
+const NAMES: [(&str, char); 12] = [
+    (".git", '\u{e5fb}'),
+    (".github", '\u{e5fd}'),
+    (".gitignore", '\u{e702}'),
+    (".gitmodules", '\u{e702}'),
+    ("cargo.toml", '\u{e7a8}'),
+    ("cargo.lock", '\u{e7a8}'),
+    ("dockerfile", '\u{f308}'),
+    ("license", '\u{f718}'),
+    ("makefile", '\u{f489}'),
+    ("node_modules", '\u{e5fa}'),
+    ("readme", '\u{f48a}'),
+    ("target", '\u{f487}'),
+];
+
+// Files by their extension, compared ignoring case.
+const EXTENSIONS: [(&str, char); 45] = [
+    ("rs", '\u{e7a8}'),
+    ("c", '\u{e61e}'),
+    ("h", '\u{e61e}'),
+    ("cpp", '\u{e61d}'),
+    ("hpp", '\u{e61d}'),
+    ("go", '\u{e626}'),
+    ("py", '\u{e606}'),
+    ("rb", '\u{e739}'),
+    ("java", '\u{e738}'),
+    ("js", '\u{e74e}'),
+    ("ts", '\u{e628}'),
+    ("lua", '\u{e620}'),
+    ("html", '\u{e736}'),
+    ("css", '\u{e749}'),
+    ("json", '\u{e60b}'),
+    ("toml", '\u{e615}'),
+    ("yaml", '\u{e615}'),
+    ("yml", '\u{e615}'),
+    ("xml", '\u{f05c0}'),
+    ("md", '\u{e609}'),
+    ("txt", '\u{f15c}'),
+    ("pdf", '\u{f1c1}'),
+    ("sh", '\u{f489}'),
+    ("bash", '\u{f489}'),
+    ("zsh", '\u{f489}'),
+    ("lock", '\u{f023}'),
+    ("png", '\u{f1c5}'),
+    ("jpg", '\u{f1c5}'),
+    ("jpeg", '\u{f1c5}'),
+    ("gif", '\u{f1c5}'),
+    ("svg", '\u{f1c5}'),
+    ("webp", '\u{f1c5}'),
+    ("mp3", '\u{f1c7}'),
+    ("flac", '\u{f1c7}'),
+    ("wav", '\u{f1c7}'),
+    ("mp4", '\u{f1c8}'),
+    ("mkv", '\u{f1c8}'),
+    ("webm", '\u{f1c8}'),
+    ("zip", '\u{f1c6}'),
+    ("tar", '\u{f1c6}'),
+    ("gz", '\u{f1c6}'),
+    ("xz", '\u{f1c6}'),
+    ("zst", '\u{f1c6}'),
+    ("7z", '\u{f1c6}'),
+    ("iso", '\u{f1c6}'),
+];
+
+pub fn icon(path: &Path, node_type: NodeType) -> char {
+    let is_symlink = std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    if is_symlink {
+        return match node_type {
+            NodeType::Dir => SYMLINK_DIR,
+            NodeType::File => SYMLINK,
+        };
+    }
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let stem = name.split('.').next().unwrap_or("");
+    if let Some((_, icon)) = NAMES
+        .iter()
+        .find(|(known, _)| *known == name || *known == stem)
+    {
+        return *icon;
+    }
+
+    if node_type == NodeType::Dir {
+        return DIR;
+    }
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    extension
+        .and_then(|extension| {
+            EXTENSIONS
+                .iter()
+                .find(|(known, _)| *known == extension)
+                .map(|(_, icon)| *icon)
+        })
+        .unwrap_or(FILE)
+}
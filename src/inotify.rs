@@ -0,0 +1,185 @@
+// With --watch the size columns follow changes on disk, like a live ncdu
+// while a build or a download runs. inotify reports what changes in each
+// scanned directory; the directory and those above it up to the root are
+// then measured again, while everything else keeps its value. Changes are
+// collected for UPDATE_INTERVAL before anything is measured, so a build
+// writing thousands of files doesn't keep the workers busy.
+//
+// Entries created or removed are put into the tree or taken out of it right
+// away, and new directories are watched as well. The rest of the tree is
+// watched once it is scanned, not on every redraw.
+
+use crate::{fileops::Change, NodeType, TreeNode};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CString, OsStr},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+const EVENTS: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MODIFY
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DELETE_SELF;
+
+pub struct Inotify {
+    fd: libc::c_int,
+    // The directory of each watch descriptor.
+    watches: HashMap<libc::c_int, PathBuf>,
+    watched: HashSet<PathBuf>,
+    // Directories whose contents changed since the last update.
+    changed: HashSet<PathBuf>,
+    // Entries created or removed since the last call to changes.
+    changes: Vec<Change>,
+    updated_at: Instant,
+}
+
+impl Inotify {
+    pub fn new() -> io::Result<Inotify> {
+        // SAFETY: no pointers are involved.
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Inotify {
+            fd,
+            watches: HashMap::new(),
+            watched: HashSet::new(),
+            changed: HashSet::new(),
+            changes: Vec::new(),
+            updated_at: Instant::now(),
+        })
+    }
+
+    // Watches the directories of the tree that aren't watched yet.
+    // Directories that can't be watched, e.g. past the limit on watches,
+    // are left out.
+    pub fn watch(&mut self, root: &TreeNode, path: &Path) {
+        if !self.watched.contains(path) {
+            self.watched.insert(path.to_path_buf());
+            let name = CString::new(path.as_os_str().as_bytes()).unwrap_or_default();
+            // SAFETY: the name is a valid C string for the whole call.
+            let wd = unsafe { libc::inotify_add_watch(self.fd, name.as_ptr(), EVENTS) };
+            if wd >= 0 {
+                self.watches.insert(wd, path.to_path_buf());
+            }
+        }
+
+        for child in &root.children {
+            if child.node_type == NodeType::Dir {
+                self.watch(child, &path.join(&child.val));
+            }
+        }
+    }
+
+    // Watches the directories of the tree from `path` down, after they were
+    // read again or appeared.
+    pub fn watch_below(&mut self, root: &TreeNode, dirname: &Path, path: &Path) {
+        let relative = match path.strip_prefix(dirname) {
+            Ok(relative) => relative,
+            Err(_) => return,
+        };
+        let node = relative.iter().try_fold(root, |node, component| {
+            let val = component.to_string_lossy();
+            node.children.iter().find(|child| child.val == val)
+        });
+        if let Some(node) = node {
+            self.watch(node, path);
+        }
+    }
+
+    // The entries created and removed since the last call, to update the
+    // tree with.
+    pub fn changes(&mut self) -> Vec<Change> {
+        self.read_events();
+        std::mem::take(&mut self.changes)
+    }
+
+    // The directories to measure again, at most once per interval: those
+    // with changes in them and all above them, up to the root.
+    pub fn changed(&mut self, root: &Path) -> Vec<PathBuf> {
+        self.read_events();
+        if self.changed.is_empty() || self.updated_at.elapsed() < UPDATE_INTERVAL {
+            return Vec::new();
+        }
+        self.updated_at = Instant::now();
+
+        let mut stale = HashSet::new();
+        for dir in self.changed.drain() {
+            for ancestor in dir.ancestors() {
+                if !stale.insert(ancestor.to_path_buf()) || ancestor == root {
+                    break;
+                }
+            }
+        }
+        stale.into_iter().collect()
+    }
+
+    fn read_events(&mut self) {
+        let mut buffer = [0u8; 4096];
+        loop {
+            // SAFETY: the buffer is writable for its whole length.
+            let read = unsafe {
+                libc::read(
+                    self.fd,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                )
+            };
+            if read <= 0 {
+                break;
+            }
+
+            let mut offset = 0;
+            while offset < read as usize {
+                // SAFETY: the kernel only writes whole events, each a
+                // header followed by `len` bytes of name.
+                let event = unsafe {
+                    std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::inotify_event)
+                };
+                let start = offset + std::mem::size_of::<libc::inotify_event>();
+                offset = start + event.len as usize;
+                // The name is padded with null bytes.
+                let name = buffer[start..offset].split(|&byte| byte == 0).next();
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    // Events were lost, so anything may have changed.
+                    self.changed.extend(self.watches.values().cloned());
+                } else if event.mask & libc::IN_IGNORED != 0 {
+                    if let Some(dir) = self.watches.remove(&event.wd) {
+                        self.watched.remove(&dir);
+                    }
+                } else if let Some(dir) = self.watches.get(&event.wd) {
+                    self.changed.insert(dir.clone());
+                    let path = match name.filter(|name| !name.is_empty()) {
+                        Some(name) => dir.join(OsStr::from_bytes(name)),
+                        None => continue,
+                    };
+                    if event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                        let node_type = if event.mask & libc::IN_ISDIR != 0 {
+                            NodeType::Dir
+                        } else {
+                            NodeType::File
+                        };
+                        self.changes.push(Change::Created(path, node_type));
+                    } else if event.mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                        self.changes.push(Change::Removed(path));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        // SAFETY: the descriptor is owned and closed only here.
+        unsafe { libc::close(self.fd) };
+    }
+}
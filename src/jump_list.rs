@@ -0,0 +1,66 @@
+// The lists that open over the tree to jump somewhere else, the projects,
+// the recent entries, the best matches, the names that aren't portable and
+// the palettes, all keep one entry selected and move it with Up and Down.
+
+pub struct JumpList<T> {
+    pub entries: Vec<T>,
+    pub selected: usize,
+    // Whether moving past either end continues at the other.
+    wraps: bool,
+}
+
+impl<T> Default for JumpList<T> {
+    fn default() -> JumpList<T> {
+        JumpList::new(Vec::new())
+    }
+}
+
+impl<T> JumpList<T> {
+    pub fn new(entries: Vec<T>) -> JumpList<T> {
+        JumpList {
+            entries,
+            selected: 0,
+            wraps: false,
+        }
+    }
+
+    pub fn wrapping(entries: Vec<T>, selected: usize) -> JumpList<T> {
+        JumpList {
+            entries,
+            selected,
+            wraps: true,
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+        } else if self.wraps {
+            self.selected = 0;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+        } else if self.wraps {
+            self.selected = self.entries.len().saturating_sub(1);
+        }
+    }
+
+    pub fn selected(&self) -> Option<&T> {
+        self.entries.get(self.selected)
+    }
+}
+
+impl<T: PartialEq> JumpList<T> {
+    // Keeps the selected entry selected if it is still listed, and the
+    // same row otherwise.
+    pub fn set_entries(&mut self, entries: Vec<T>) {
+        let selected = self
+            .selected()
+            .and_then(|selected| entries.iter().position(|entry| entry == selected));
+        self.selected = selected.unwrap_or(self.selected.min(entries.len().saturating_sub(1)));
+        self.entries = entries;
+    }
+}
@@ -0,0 +1,313 @@
+// Chords are short key sequences typed in navigation mode, like vim's `gg`.
+// They are bound with `--bind <keys>=<action>`, which adds to or replaces
+// the default bindings. A key that starts a chord is held back until the
+// chord is complete. If the next key doesn't continue it the held keys are
+// used for type-ahead instead, and if it isn't typed within CHORD_TIMEOUT
+// they are dropped.
+//
+// Keys written in angle brackets, like `<PageDown>` or `<Ctrl+q>`, are bound
+// on their own instead and work in search mode too, ahead of what the key
+// does by default. Binding one to `none` turns it off.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::time::Duration;
+
+pub const CHORD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    Top,
+    Bottom,
+    Parent,
+    Hide,
+    Yank,
+    Jump,
+    Delete,
+    Rename,
+    Create,
+    Fold,
+    Mark,
+    Sort,
+    Reverse,
+    Dotfiles,
+    Deeper,
+    Shallower,
+    Retry,
+    Conflicts,
+    Compact,
+    DirsFirst,
+    Binaries,
+    Collapse,
+    Expand,
+    Search,
+    PageUp,
+    PageDown,
+    Select,
+    Clear,
+    Quit,
+    None,
+}
+
+pub const ACTION_NAMES: [&str; 32] = [
+    "up",
+    "down",
+    "top",
+    "bottom",
+    "parent",
+    "hide",
+    "yank",
+    "jump",
+    "delete",
+    "rename",
+    "create",
+    "fold",
+    "mark",
+    "sort",
+    "reverse",
+    "dotfiles",
+    "deeper",
+    "shallower",
+    "retry",
+    "conflicts",
+    "compact",
+    "dirs-first",
+    "binaries",
+    "collapse",
+    "expand",
+    "search",
+    "page-up",
+    "page-down",
+    "select",
+    "clear",
+    "quit",
+    "none",
+];
+
+fn action(name: &str) -> Option<Action> {
+    match name {
+        "up" => Some(Action::Up),
+        "down" => Some(Action::Down),
+        "top" => Some(Action::Top),
+        "bottom" => Some(Action::Bottom),
+        "parent" => Some(Action::Parent),
+        "hide" => Some(Action::Hide),
+        "yank" => Some(Action::Yank),
+        "jump" => Some(Action::Jump),
+        "delete" => Some(Action::Delete),
+        "rename" => Some(Action::Rename),
+        "create" => Some(Action::Create),
+        "fold" => Some(Action::Fold),
+        "mark" => Some(Action::Mark),
+        "sort" => Some(Action::Sort),
+        "reverse" => Some(Action::Reverse),
+        "dotfiles" => Some(Action::Dotfiles),
+        "deeper" => Some(Action::Deeper),
+        "shallower" => Some(Action::Shallower),
+        "retry" => Some(Action::Retry),
+        "conflicts" => Some(Action::Conflicts),
+        "compact" => Some(Action::Compact),
+        "dirs-first" => Some(Action::DirsFirst),
+        "binaries" => Some(Action::Binaries),
+        "collapse" => Some(Action::Collapse),
+        "expand" => Some(Action::Expand),
+        "search" => Some(Action::Search),
+        "page-up" => Some(Action::PageUp),
+        "page-down" => Some(Action::PageDown),
+        "select" => Some(Action::Select),
+        "clear" => Some(Action::Clear),
+        "quit" => Some(Action::Quit),
+        "none" => Some(Action::None),
+        _ => None,
+    }
+}
+
+const DEFAULT_BINDINGS: [&str; 23] = [
+    "j=down",
+    "k=up",
+    "h=collapse",
+    "l=expand",
+    "/=search",
+    "gg=top",
+    "G=bottom",
+    "dd=hide",
+    "y=yank",
+    "D=delete",
+    "r=rename",
+    "a=create",
+    " =fold",
+    "m=mark",
+    "s=sort",
+    "S=reverse",
+    "+=deeper",
+    "-=shallower",
+    "R=retry",
+    "gc=conflicts",
+    "zc=compact",
+    "zd=dirs-first",
+    "zb=binaries",
+];
+
+pub enum Chord {
+    Complete(Action),
+    Partial,
+    Unbound,
+}
+
+#[derive(Clone)]
+pub struct Keymap {
+    chords: Vec<(String, Action)>,
+    keys: Vec<((KeyModifiers, KeyCode), Action)>,
+}
+
+impl Keymap {
+    pub fn new(bindings: &[String]) -> Result<Keymap, String> {
+        let mut keymap = Keymap {
+            chords: Vec::new(),
+            keys: Vec::new(),
+        };
+        for binding in DEFAULT_BINDINGS
+            .iter()
+            .copied()
+            .chain(bindings.iter().map(|b| b.as_str()))
+        {
+            keymap.bind(binding)?;
+        }
+        Ok(keymap)
+    }
+
+    fn bind(&mut self, binding: &str) -> Result<(), String> {
+        let (keys, name) = binding
+            .split_once('=')
+            .ok_or(format!("{}: expected <keys>=<action>", binding))?;
+        if keys.is_empty() {
+            return Err(format!("{}: no keys given", binding));
+        }
+        let action = action(name).ok_or(format!(
+            "{}: unknown action, expected one of {}",
+            binding,
+            ACTION_NAMES.join(", ")
+        ))?;
+
+        if let Some(spec) = keys.strip_prefix('<').and_then(|k| k.strip_suffix('>')) {
+            let key = parse_key(spec).ok_or(format!("{}: unknown key {}", binding, keys))?;
+            self.keys.retain(|(bound, _)| *bound != key);
+            self.keys.push((key, action));
+            return Ok(());
+        }
+
+        self.chords.retain(|(bound, _)| bound != keys);
+        self.chords.push((keys.to_string(), action));
+        Ok(())
+    }
+
+    // The action a key is bound to on its own.
+    pub fn key(&self, key: &KeyEvent) -> Option<Action> {
+        let key = normalize(key.modifiers, key.code);
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn lookup(&self, keys: &str) -> Chord {
+        if let Some((_, action)) = self.chords.iter().find(|(bound, _)| bound == keys) {
+            return Chord::Complete(*action);
+        }
+
+        if self.chords.iter().any(|(bound, _)| bound.starts_with(keys)) {
+            Chord::Partial
+        } else {
+            Chord::Unbound
+        }
+    }
+}
+
+// Parses a key such as `Ctrl+q`, `Alt+Enter` or `F5`. Names are matched
+// ignoring case, and anything else has to be a single character.
+fn parse_key(spec: &str) -> Option<(KeyModifiers, KeyCode)> {
+    // The key itself may be a +, as in `Ctrl++`.
+    let (prefixes, name) = match spec.rsplit_once('+') {
+        Some((rest, "")) => (rest.strip_suffix('+').unwrap_or(rest), "+"),
+        Some((prefixes, name)) => (prefixes, name),
+        None => ("", spec),
+    };
+    let mut modifiers = KeyModifiers::NONE;
+    for prefix in prefixes.split('+').filter(|prefix| !prefix.is_empty()) {
+        modifiers |= match prefix.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+
+    let code = match name.to_lowercase().as_str() {
+        _ if name.chars().count() == 1 && modifiers == KeyModifiers::SHIFT => {
+            KeyCode::Char(name.chars().flat_map(char::to_uppercase).next()?)
+        }
+        "esc" => KeyCode::Esc,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        lower => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        },
+    };
+    Some(normalize(modifiers, code))
+}
+
+// Terminals report Shift with a character as the shifted character, with or
+// without the Shift modifier, and Ctrl with a character as either case, so
+// those are compared without them.
+fn normalize(modifiers: KeyModifiers, code: KeyCode) -> (KeyModifiers, KeyCode) {
+    let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+    match code {
+        KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => (
+            modifiers - KeyModifiers::SHIFT,
+            KeyCode::Char(c.to_ascii_lowercase()),
+        ),
+        KeyCode::Char(c) => (modifiers - KeyModifiers::SHIFT, KeyCode::Char(c)),
+        code => (modifiers, code),
+    }
+}
+
+// A key as the terminal reported it, e.g. "Ctrl+Alt+Char('x')", for the key
+// debug overlay. Terminals that send an unexpected sequence show up as the
+// wrong key or modifiers here.
+pub fn describe_key(key: &KeyEvent) -> String {
+    let mut description = String::new();
+    for (modifier, name) in [
+        (KeyModifiers::CONTROL, "Ctrl+"),
+        (KeyModifiers::ALT, "Alt+"),
+        (KeyModifiers::SHIFT, "Shift+"),
+        (KeyModifiers::SUPER, "Super+"),
+    ] {
+        if key.modifiers.contains(modifier) {
+            description.push_str(name);
+        }
+    }
+    description.push_str(&format!("{:?}", key.code));
+    if key.kind != KeyEventKind::Press {
+        description.push_str(&format!(" ({:?})", key.kind));
+    }
+    description
+}
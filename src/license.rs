@@ -0,0 +1,75 @@
+// Before a release every source file should start with a license header.
+// `--lint-headers` lists the source files without one instead of starting
+// the TUI, and the license column flags them in the tree. A header is
+// anything in the first lines of a file that the regex given with
+// --license-header matches, by default a copyright notice or an SPDX
+// identifier. Source files are told apart from the rest by their extension.
+
+use crate::{NodeType, TreeNode};
+use regex::Regex;
+use std::{
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+pub const DEFAULT_HEADER: &str = r"(?i)copyright|spdx-license-identifier";
+
+// How many lines at the top of a file make up its header.
+const HEADER_LINES: usize = 20;
+
+const SOURCE_EXTENSIONS: [&str; 27] = [
+    "c", "cc", "cpp", "cs", "css", "cxx", "go", "h", "hpp", "java", "js", "jsx", "kt", "lua", "m",
+    "php", "pl", "py", "rb", "rs", "scala", "scss", "sh", "swift", "ts", "tsx", "zig",
+];
+
+// The regex given with --license-header.
+static HEADER: OnceLock<Regex> = OnceLock::new();
+
+pub fn set_header(header: Regex) {
+    let _ = HEADER.set(header);
+}
+
+pub fn header() -> &'static Regex {
+    HEADER.get_or_init(|| Regex::new(DEFAULT_HEADER).unwrap())
+}
+
+pub fn is_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SOURCE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+// Whether a source file starts with a header. Files that can't be read are
+// taken to have one, as there is nothing to fix in them.
+pub fn has_header(path: &Path, header: &Regex) -> bool {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return true,
+    };
+    BufReader::new(file)
+        .split(b'\n')
+        .take(HEADER_LINES)
+        .map_while(Result::ok)
+        .any(|line| header.is_match(&String::from_utf8_lossy(&line)))
+}
+
+// The source files of a tree without a header.
+pub fn missing_headers(root: &TreeNode, path: &Path, header: &Regex) -> Vec<PathBuf> {
+    let mut missing = Vec::new();
+    find_missing(root, path, header, &mut missing);
+    missing
+}
+
+fn find_missing(root: &TreeNode, path: &Path, header: &Regex, missing: &mut Vec<PathBuf>) {
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        if child.node_type == NodeType::File
+            && is_source(&child_path)
+            && !has_header(&child_path, header)
+        {
+            missing.push(child_path.clone());
+        }
+        find_missing(child, &child_path, header, missing);
+    }
+}
@@ -0,0 +1,112 @@
+// With --lock, an instance takes a lock on the directory it shows, so that a
+// second instance started on the same directory by accident can warn before
+// scanning it again. The lock is an flock on a file named after the
+// directory in the runtime directory. The kernel releases it when the
+// process ends, however it ends, so a crash or a kill never leaves a stale
+// lock behind. Elsewhere than on Unix there is no locking.
+//
+// Without a runtime directory the lock goes to the shared temporary
+// directory, where another user could have put a symlink or a hard link to
+// one of our files under the lock's name. The file is therefore opened
+// without following symlinks and only used when it is ours and has no
+// other names.
+
+use crate::util::{fnv1a, FNV_OFFSET};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+pub struct Lock {
+    // The lock is held as long as the file is open.
+    _file: File,
+}
+
+pub enum Acquired {
+    Locked(Lock),
+    // Another instance holds the lock. Its process id, if it could be read.
+    Held(Option<u32>),
+}
+
+pub fn acquire(root: &Path) -> io::Result<Acquired> {
+    let path = lock_path(root);
+    let mut options = File::options();
+    options.read(true).write(true).create(true).truncate(false);
+    let mut file = no_follow(&mut options).open(path)?;
+    check_owner(&file)?;
+
+    if !try_lock(&file)? {
+        let mut pid = String::new();
+        file.read_to_string(&mut pid)?;
+        return Ok(Acquired::Held(pid.trim().parse().ok()));
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    Ok(Acquired::Locked(Lock { _file: file }))
+}
+
+fn lock_path(root: &Path) -> PathBuf {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::temp_dir(),
+    };
+    dir.join(format!(
+        "tree-rs-{:016x}.lock",
+        fnv1a(FNV_OFFSET, root.to_string_lossy().as_bytes())
+    ))
+}
+
+#[cfg(unix)]
+fn no_follow(options: &mut OpenOptions) -> &mut OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    options.custom_flags(libc::O_NOFOLLOW)
+}
+
+#[cfg(not(unix))]
+fn no_follow(options: &mut OpenOptions) -> &mut OpenOptions {
+    options
+}
+
+#[cfg(unix)]
+fn check_owner(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = file.metadata()?;
+    // SAFETY: geteuid can't fail.
+    if metadata.uid() != unsafe { libc::geteuid() } || metadata.nlink() != 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the lock file isn't ours",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_owner(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor is open for as long as the file is.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EWOULDBLOCK) => Ok(false),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> io::Result<bool> {
+    Ok(true)
+}
@@ -0,0 +1,156 @@
+// Entries are colored like ls colors them, following the LS_COLORS variable
+// that dircolors sets up. It is a list of `key=SGR codes` separated by
+// colons, where the key is either a two letter file type such as `di` for
+// directories and `ex` for executables, or a pattern such as `*.rs`
+// matching the end of file names.
+
+use std::{fs::Metadata, path::Path};
+use tui::style::{Color, Modifier, Style};
+
+pub struct LsColors {
+    types: Vec<(String, String)>,
+    // Patterns without the leading *, lowercased.
+    suffixes: Vec<(String, String)>,
+}
+
+impl LsColors {
+    // None when LS_COLORS isn't set.
+    pub fn from_env() -> Option<LsColors> {
+        let value = std::env::var("LS_COLORS").ok()?;
+        if value.is_empty() {
+            return None;
+        }
+
+        let mut colors = LsColors {
+            types: Vec::new(),
+            suffixes: Vec::new(),
+        };
+        for entry in value.split(':') {
+            let (key, codes) = match entry.split_once('=') {
+                Some(entry) => entry,
+                None => continue,
+            };
+            match key.strip_prefix('*') {
+                Some(suffix) => colors
+                    .suffixes
+                    .push((suffix.to_lowercase(), codes.to_string())),
+                None => colors.types.push((key.to_string(), codes.to_string())),
+            }
+        }
+        Some(colors)
+    }
+
+    fn type_codes(&self, key: &str) -> Option<&str> {
+        self.types
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, codes)| codes.as_str())
+    }
+
+    // The SGR codes for an entry, e.g. "01;34", if any apply.
+    pub fn codes(&self, path: &Path) -> Option<&str> {
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        let kind = kind(path, &metadata);
+
+        // Patterns only apply to regular files, as in ls.
+        if kind == "fi" {
+            let name = path.file_name()?.to_string_lossy().to_lowercase();
+            if let Some((_, codes)) = self
+                .suffixes
+                .iter()
+                .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            {
+                return Some(codes);
+            }
+        }
+
+        self.type_codes(kind)
+            .or_else(|| match kind {
+                "or" => self.type_codes("ln"),
+                _ => None,
+            })
+            .filter(|codes| !codes.is_empty())
+    }
+
+    pub fn style(&self, path: &Path) -> Option<Style> {
+        self.codes(path).map(style)
+    }
+}
+
+// The LS_COLORS key of an entry's type.
+fn kind(path: &Path, metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        return if path.exists() { "ln" } else { "or" };
+    }
+    if file_type.is_dir() {
+        return "di";
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        if file_type.is_fifo() {
+            return "pi";
+        }
+        if file_type.is_socket() {
+            return "so";
+        }
+        if file_type.is_block_device() {
+            return "bd";
+        }
+        if file_type.is_char_device() {
+            return "cd";
+        }
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return "ex";
+        }
+    }
+
+    "fi"
+}
+
+// Translates SGR codes such as "01;38;5;208" into a style for the TUI.
+// Codes it doesn't know are skipped.
+pub fn style(codes: &str) -> Style {
+    let codes: Vec<u8> = codes.split(';').filter_map(|c| c.parse().ok()).collect();
+    let mut style = Style::default();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => style = Style::default(),
+            1 => style = style.add_modifier(Modifier::BOLD),
+            2 => style = style.add_modifier(Modifier::DIM),
+            3 => style = style.add_modifier(Modifier::ITALIC),
+            4 => style = style.add_modifier(Modifier::UNDERLINED),
+            5 => style = style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style = style.add_modifier(Modifier::REVERSED),
+            code @ 30..=37 => style = style.fg(Color::Indexed(code - 30)),
+            code @ 90..=97 => style = style.fg(Color::Indexed(code - 90 + 8)),
+            code @ 40..=47 => style = style.bg(Color::Indexed(code - 40)),
+            code @ 100..=107 => style = style.bg(Color::Indexed(code - 100 + 8)),
+            code @ (38 | 48) => {
+                let color = match codes.get(i + 1) {
+                    Some(5) => codes.get(i + 2).map(|&n| (Color::Indexed(n), 3)),
+                    Some(2) => match codes.get(i + 2..i + 5) {
+                        Some(&[r, g, b]) => Some((Color::Rgb(r, g, b), 5)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let Some((color, length)) = color {
+                    style = if code == 38 {
+                        style.fg(color)
+                    } else {
+                        style.bg(color)
+                    };
+                    i += length - 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    style
+}
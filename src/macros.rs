@@ -0,0 +1,73 @@
+// Macros record keys so that they can be replayed. In navigation mode `q`
+// followed by a register letter starts recording every key typed into that
+// register and `q` stops it. `@` followed by the register replays it, `@@`
+// replays the last macro again, and a count typed before `@` replays it that
+// many times.
+
+use crossterm::event::KeyEvent;
+use std::collections::{HashMap, VecDeque};
+
+// Guards against a macro that replays itself.
+const MAX_QUEUED_KEYS: usize = 10000;
+
+#[derive(Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<KeyEvent>>,
+    recording: Option<(char, Vec<KeyEvent>)>,
+    last: Option<char>,
+    queue: VecDeque<KeyEvent>,
+}
+
+impl Macros {
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn start(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    // Stops recording. The key that stopped it has already been recorded
+    // and is dropped.
+    pub fn stop(&mut self) {
+        if let Some((register, mut keys)) = self.recording.take() {
+            keys.pop();
+            self.registers.insert(register, keys);
+        }
+    }
+
+    pub fn record(&mut self, key: KeyEvent) {
+        if let Some((_, keys)) = self.recording.as_mut() {
+            keys.push(key);
+        }
+    }
+
+    // Queues the keys of a register. Returns false if it is empty.
+    pub fn replay(&mut self, register: char, count: usize) -> bool {
+        let register = match register {
+            '@' => match self.last {
+                Some(last) => last,
+                None => return false,
+            },
+            register => register,
+        };
+
+        let keys = match self.registers.get(&register) {
+            Some(keys) if !keys.is_empty() => keys,
+            _ => return false,
+        };
+
+        for _ in 0..count {
+            if self.queue.len() + keys.len() > MAX_QUEUED_KEYS {
+                break;
+            }
+            self.queue.extend(keys.iter().copied());
+        }
+        self.last = Some(register);
+        true
+    }
+
+    pub fn next_key(&mut self) -> Option<KeyEvent> {
+        self.queue.pop_front()
+    }
+}
@@ -1,19 +1,112 @@
+pub mod annotate;
+pub mod backend;
+pub mod batch;
+pub mod budget;
+pub mod config;
+pub mod content;
+pub mod debug_log;
+pub mod details;
+pub mod events;
+pub mod fileops;
+pub mod format;
+pub mod git;
+pub mod i18n;
+pub mod icons;
+#[cfg(target_os = "linux")]
+pub mod inotify;
+pub mod jump_list;
+pub mod keymap;
+pub mod license;
+pub mod lock;
+pub mod ls_colors;
+pub mod macros;
+pub mod palette;
+pub mod pattern;
+pub mod plugins;
+pub mod portability;
+pub mod project;
+pub mod quick;
+pub mod recent;
 pub mod render;
+pub mod scaffold;
+pub mod screenshot;
+pub mod scripting;
+pub mod snapshot;
+pub mod status;
+pub mod storage;
+pub mod tags;
+#[cfg(target_os = "linux")]
+pub mod uring;
 pub mod util;
+pub mod watch;
+pub mod wizard;
+pub mod xattr;
 
-use crate::render::{print_tree, render};
-use crate::util::{filter_tree, print_node_name, term_setup, term_teardown};
-use clap::{arg, command, ArgGroup, Command};
-use crossterm::event::{self, Event, KeyCode};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{path::PathBuf, time::Duration};
-use tokio::sync::mpsc;
-use tokio::task;
+use crate::annotate::{human_size, leading_number, provider, MARKER_PATTERN, PROVIDER_NAMES};
+use crate::backend::{backend, StatBackend, BACKEND_NAMES};
+use crate::batch::{include_list, json_tree, mkdir_script, print_batch, DEFAULT_BATCH_FORMAT};
+use crate::budget::{limit, Budget};
+use crate::config::{config_args, config_file_arg, config_path, profile_arg, set_config_file};
+use crate::content::{content_tree, first_match};
+use crate::details::details;
+use crate::events::{emit_matches, emit_scan};
+use crate::fileops::Prompt;
+use crate::format::{format_node, NodeInfo, TimeFormat, FORMAT_HELP, TIME_FORMAT_HELP};
+use crate::git::GitStatus;
+use crate::i18n::{tr, trf};
+use crate::icons::icon;
+use crate::jump_list::JumpList;
+use crate::keymap::{Keymap, ACTION_NAMES};
+use crate::license::{missing_headers, set_header, DEFAULT_HEADER};
+use crate::lock::{acquire, Acquired};
+use crate::ls_colors::LsColors;
+use crate::palette::{Palette, ThemePicker, PALETTE_NAMES};
+use crate::pattern::{
+    compile_pattern, describe_match, fuzzy_positions, fuzzy_score, matches_paths, MatchMode,
+    Search, MATCH_MODE_NAMES,
+};
+use crate::portability::{portability_report, Finding};
+use crate::project::Project;
+use crate::quick::best_matches;
+use crate::recent::remember;
+use crate::render::{
+    add_symbols, flatten_tree, jump_label, print_tree, render, view, view_path, Line, Outcome,
+    State, KEY_LOG_LENGTH,
+};
+use crate::scaffold::Scaffold;
+use crate::snapshot::{set_snapshot, Snapshot};
+use crate::status::{format_status, StatusInfo, DEFAULT_STATUS_FORMAT};
+use crate::storage::{network_mounts, virtual_mounts, Storage, Tuning, STORAGE_NAMES};
+use crate::tags::Tags;
+use crate::util::{
+    ansi_supported, case_conflicts, compact_tree, count_matches, count_path_matches, display_path,
+    filter_tree, filter_tree_by_path, get_tree_count, is_capable_terminal, is_case_insensitive,
+    is_dotfile, is_macos_metadata, node_name, only_case_conflicts, prune_tree, relative_path,
+    reverse_tree, sort_tree_by, sort_tree_by_key, sort_tree_by_score,
+};
+use crate::wizard::run_wizard;
+use clap::{arg, command, ArgAction, ArgGroup, ArgMatches, Command};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::OsString,
+    io::{BufRead, IsTerminal},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, info, warn};
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::Rect,
+    style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
 
@@ -32,61 +125,228 @@ pub struct TreeNode {
 
 pub enum ColorOptions {
     Default,
+    Depth,
     NoColor,
 }
 
-fn read_dir_incremental(root: &mut TreeNode, dirname: PathBuf, limit: &mut i32) {
-    root.color = 33;
-    root.val = dirname.file_name().unwrap().to_str().unwrap().to_string();
+pub enum SelectionStyle {
+    Name,
+    Bar,
+}
+
+impl SelectionStyle {
+    fn is_bar(&self) -> bool {
+        matches!(self, SelectionStyle::Bar)
+    }
+}
+
+pub enum ZeroMatchAlert {
+    None,
+    Bell,
+    Flash,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum SortKey {
+    Name,
+    // The newest first.
+    Mtime,
+    // By extension, then by name.
+    Extension,
+    // Directories before files.
+    Type,
+    // The largest first.
+    Column(String),
+}
+
+const SORT_NAMES: [&str; 4] = ["name", "mtime", "extension", "type"];
+
+impl SortKey {
+    fn from_name(name: &str) -> SortKey {
+        match name {
+            "name" => SortKey::Name,
+            "mtime" => SortKey::Mtime,
+            "extension" => SortKey::Extension,
+            "type" => SortKey::Type,
+            column => SortKey::Column(column.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Mtime => "mtime",
+            SortKey::Extension => "extension",
+            SortKey::Type => "type",
+            SortKey::Column(column) => column,
+        }
+    }
+
+    // The next key the s key switches to. Sizes are always set up, but
+    // other columns are only sorted by when they are asked for.
+    pub fn next(&self) -> SortKey {
+        match self {
+            SortKey::Name => SortKey::Column("size".to_string()),
+            SortKey::Column(_) => SortKey::Mtime,
+            SortKey::Mtime => SortKey::Extension,
+            SortKey::Extension => SortKey::Type,
+            SortKey::Type => SortKey::Name,
+        }
+    }
+}
+
+pub struct DisplayOptions {
+    color: ColorOptions,
+    // What the root is shown as.
+    root_label: String,
+    ls_colors: Option<LsColors>,
+    icons: bool,
+    zebra: bool,
+    selection_style: SelectionStyle,
+    status_format: String,
+    zero_match_alert: ZeroMatchAlert,
+    regex_debug: bool,
+    git: bool,
+    format: Option<String>,
+    emit_events: bool,
+    script: Option<PathBuf>,
+    columns: Vec<String>,
+    sort: SortKey,
+    reverse: bool,
+    fold_level: Option<usize>,
+    budgets: Vec<Budget>,
+    show_size: bool,
+    max_depth: Option<usize>,
+    keymap: Keymap,
+    // Like vim: start in navigation mode, and Esc goes back to it from
+    // search mode.
+    vim: bool,
+    screen_reader: bool,
+    // The name of the palette, which the theme picker can change.
+    palette: String,
+    time_format: TimeFormat,
+    show_hidden: bool,
+    hide_binaries: bool,
+    // Names left out with --ignore.
+    ignore: Vec<Regex>,
+    only_case_conflicts: bool,
+    compact: bool,
+    dirs_first: bool,
+    // Whether sizes are measured again as files change.
+    watch: bool,
+    show_macos_metadata: bool,
+    match_mode: MatchMode,
+    // Whether the pattern is matched against file contents.
+    content: bool,
+    // How many matches the quick view lists.
+    top: usize,
+    tags: Option<Tags>,
+    case_insensitive: bool,
+    tuning: Tuning,
+    backend: StatBackend,
+    page_size: Option<usize>,
+    mouse: bool,
+    screenshot: Option<PathBuf>,
+    key_debug: bool,
+    tick: Duration,
+    from_stdin: bool,
+    // With --scaffold the layout to create, shown instead of scanning.
+    scaffold: Option<Scaffold>,
+}
+
+// Exit codes, so scripts can tell what happened. Quitting the TUI without
+// selecting an entry counts as aborting.
+const EXIT_ABORTED: u8 = 1;
+const EXIT_SCAN_ERROR: u8 = 2;
+const EXIT_INVALID_ARGUMENTS: u8 = 3;
+
+// Directories and entries that couldn't be read while scanning.
+static SCAN_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+// Why each directory that couldn't be listed couldn't, for the output.
+static READ_ERRORS: Mutex<Vec<(PathBuf, String)>> = Mutex::new(Vec::new());
+
+// Mount points that are listed but not scanned.
+static SKIPPED_MOUNTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+// Directories on network mounts are listed on a thread of their own and
+// given up on after the read timeout, so a dead server holds up the scan
+// for a moment instead of forever. The directories given up on are shown
+// as timed out and aren't read again until they are retried.
+static NETWORK_MOUNTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+static READ_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+static TIMED_OUT: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// With a maximum depth, directories that deep are added without their
+// contents.
+fn read_dir_incremental(
+    root: &mut TreeNode,
+    dirname: PathBuf,
+    limit: &mut i32,
+    max_depth: Option<usize>,
+    backend: StatBackend,
+) {
+    root.color = type_color(root.node_type);
+    root.val = node_name(&dirname);
 
     *limit -= 1;
 
-    if dirname.is_file() {
-        root.node_type = NodeType::File;
+    // The type was already known when the node was created.
+    if root.node_type == NodeType::File
+        || max_depth == Some(0)
+        || is_skipped_mount(&dirname)
+        || is_timed_out(&dirname)
+        || is_symlink_loop(&dirname)
+    {
         return;
     }
 
-    root.node_type = NodeType::Dir;
-    let entries = match std::fs::read_dir(&dirname) {
-        Ok(entries) => entries,
-        Err(_) => {
+    let entries = match list_dir(&dirname, backend) {
+        Some(entries) => entries,
+        None => {
+            SCAN_ERRORS.fetch_add(1, Ordering::Relaxed);
             return;
         }
     };
 
-    let mut entries: Vec<_> = entries.collect();
-    entries.sort_by(|a, b| a.as_ref().unwrap().path().cmp(&b.as_ref().unwrap().path()));
-
-    if root.children.len() == 0 {
-        for entry in entries {
-            let path = entry.unwrap().path();
-
+    if root.children.is_empty() {
+        for (path, node_type) in entries {
             if limit == &0 {
                 return;
             }
 
-            let val = path.file_name().unwrap().to_str().unwrap().to_string();
+            let val = node_name(&path);
             root.children.push(TreeNode {
                 color: 33,
                 val,
                 children: Vec::new(),
-                node_type: NodeType::Dir,
+                node_type,
             });
 
-            read_dir_incremental(root.children.last_mut().unwrap(), path, limit);
+            read_dir_incremental(
+                root.children.last_mut().unwrap(),
+                path,
+                limit,
+                max_depth.map(|depth| depth - 1),
+                backend,
+            );
         }
     } else {
         let mut start = false;
         let last_val = root.children.last().unwrap().val.clone();
-        for entry in entries {
-            let path = entry.unwrap().path();
-
-            let val = path.file_name().unwrap().to_str().unwrap().to_string();
+        for (path, node_type) in entries {
+            let val = node_name(&path);
 
             if val == last_val {
                 start = true;
                 *limit += 1;
-                read_dir_incremental(root.children.last_mut().unwrap(), path, limit);
+                read_dir_incremental(
+                    root.children.last_mut().unwrap(),
+                    path,
+                    limit,
+                    max_depth.map(|depth| depth - 1),
+                    backend,
+                );
                 continue;
             }
 
@@ -99,98 +359,2493 @@ fn read_dir_incremental(root: &mut TreeNode, dirname: PathBuf, limit: &mut i32)
                     color: 33,
                     val,
                     children: Vec::new(),
-                    node_type: NodeType::Dir,
+                    node_type,
                 });
 
-                read_dir_incremental(root.children.last_mut().unwrap(), path, limit);
+                read_dir_incremental(
+                    root.children.last_mut().unwrap(),
+                    path,
+                    limit,
+                    max_depth.map(|depth| depth - 1),
+                    backend,
+                );
             }
         }
     }
 }
 
+// The ANSI color of an entry in the printed tree, yellow for directories
+// and blue for files.
+fn type_color(node_type: NodeType) -> i32 {
+    match node_type {
+        NodeType::Dir => 33,
+        NodeType::File => 34,
+    }
+}
+
+// Builds the tree from a list of paths, one per line, such as the output of
+// find, instead of scanning. Relative paths are taken from the current
+// directory, and paths outside of the root are left out. The directories
+// leading to a path are added even if they aren't listed themselves.
+fn read_paths(root: &mut TreeNode, dirname: &Path, input: impl BufRead) {
+    let mut paths: Vec<PathBuf> = input
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| {
+            let path = PathBuf::from(line);
+            let path = path
+                .canonicalize()
+                .or_else(|_| std::path::absolute(&path))
+                .ok()?;
+            path.strip_prefix(dirname).ok().map(Path::to_path_buf)
+        })
+        .collect();
+    // Paths compare by component, so parents come before their children
+    // and siblings end up in the order a scan would list them.
+    paths.sort();
+    paths.dedup();
+
+    for relative in paths {
+        let mut node = &mut *root;
+        let mut path = dirname.to_path_buf();
+        for component in relative.iter() {
+            path.push(component);
+            let val = component.to_string_lossy().to_string();
+            let index = match node.children.iter().position(|child| child.val == val) {
+                Some(index) => index,
+                None => {
+                    let node_type = if path.is_dir() {
+                        NodeType::Dir
+                    } else {
+                        NodeType::File
+                    };
+                    node.node_type = NodeType::Dir;
+                    node.color = type_color(NodeType::Dir);
+                    node.children.push(TreeNode {
+                        color: type_color(node_type),
+                        val,
+                        children: Vec::new(),
+                        node_type,
+                    });
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index];
+        }
+    }
+}
+
+// Orders the tree by a sort key. Modification times are remembered in
+// `mtimes`, as every entry is looked at on every refresh, until --watch or
+// a retry forgets them. `column` gives the
+// value of a column for an entry, from the annotator in the TUI and measured
+// on the spot otherwise.
+fn sort_tree_by_setting(
+    tree: &mut TreeNode,
+    path: &Path,
+    sort: &SortKey,
+    column: &dyn Fn(&str, &Path, NodeType) -> Option<u64>,
+    mtimes: &mut HashMap<PathBuf, Option<SystemTime>>,
+) {
+    match sort {
+        SortKey::Name => {}
+        SortKey::Mtime => {
+            remember_mtimes(tree, path, mtimes);
+            let mtimes = &*mtimes;
+            sort_tree_by(tree, path, &|path, _| {
+                std::cmp::Reverse(mtimes.get(path).copied().flatten())
+            });
+        }
+        SortKey::Extension => sort_tree_by(tree, path, &|path, _| {
+            path.extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+        }),
+        SortKey::Type => sort_tree_by(tree, path, &|_, node| node.node_type != NodeType::Dir),
+        SortKey::Column(name) => {
+            sort_tree_by_key(tree, path, &|path, node| column(name, path, node.node_type))
+        }
+    }
+}
+
+// Applies --reverse and then --dirs-first to a sorted tree, the same way in
+// the TUI and in every output mode. Directories go first within whatever
+// order the entries are in.
+fn order_tree(tree: &mut TreeNode, path: &Path, reverse: bool, dirs_first: bool) {
+    if reverse {
+        reverse_tree(tree);
+    }
+    if dirs_first {
+        sort_tree_by(tree, path, &|_, node| node.node_type != NodeType::Dir);
+    }
+}
+
+fn remember_mtimes(
+    root: &TreeNode,
+    path: &Path,
+    mtimes: &mut HashMap<PathBuf, Option<SystemTime>>,
+) {
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        if !mtimes.contains_key(&child_path) {
+            let mtime = std::fs::symlink_metadata(&child_path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            mtimes.insert(child_path.clone(), mtime);
+        }
+        remember_mtimes(child, &child_path, mtimes);
+    }
+}
+
+// Shows the layout in the TUI, where Enter creates it and Esc leaves
+// without, or just prints what is created.
+fn run_scaffold(
+    root: &mut TreeNode,
+    scaffold: &Scaffold,
+    dirname: &Path,
+    pattern: &str,
+    options: &DisplayOptions,
+    interactive: bool,
+    dry_run: bool,
+) -> ExitCode {
+    if interactive {
+        scaffold.add_to(root);
+        match render(root, dirname.to_path_buf(), pattern.to_string(), options) {
+            Ok(Outcome::Aborted) => return ExitCode::from(EXIT_ABORTED),
+            Ok(_) => {}
+            // The layout isn't created without being seen.
+            Err(e) => {
+                eprintln!("{}", trf("no_tui", &[&e]));
+                print!("{}", scaffold.diff(dirname));
+                return ExitCode::from(EXIT_ABORTED);
+            }
+        }
+    }
+
+    print!("{}", scaffold.diff(dirname));
+    if dry_run {
+        return ExitCode::SUCCESS;
+    }
+    match scaffold.apply(dirname) {
+        Ok(created) => {
+            eprintln!("{}", trf("scaffold_created", &[&created]));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", trf("scaffold_failed", &[&e]));
+            ExitCode::from(EXIT_SCAN_ERROR)
+        }
+    }
+}
+
+// A path relative to the root, which is . itself.
+fn relative_to(dirname: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(dirname) {
+        Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+fn list_text(paths: &[PathBuf], separator: char) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{}{}", display_path(path), separator))
+        .collect()
+}
+
+// Writes a list of paths to the --list-file, each ended by the separator.
+fn write_list(paths: &[PathBuf], separator: char, file: &str) -> ExitCode {
+    match std::fs::write(file, list_text(paths, separator)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", trf("list_failed", &[&file, &e]));
+            ExitCode::from(EXIT_SCAN_ERROR)
+        }
+    }
+}
+
+// What the root is shown as: the label if there is one, else its full
+// path, which for a root given as a symlink follows the link as given.
+// `dirname` is the canonical path of the root.
+fn root_label(given: &str, dirname: &Path, label: Option<&str>) -> String {
+    if let Some(label) = label {
+        return label.to_string();
+    }
+    let is_link = std::fs::symlink_metadata(given.trim_end_matches(['/', '\\']))
+        .is_ok_and(|metadata| metadata.file_type().is_symlink());
+    if is_link {
+        format!("{} → {}", given, display_path(dirname))
+    } else {
+        display_path(dirname)
+    }
+}
+
+// Symlinks to a directory containing them, like /usr/bin/X11 -> ., would be
+// scanned forever, so they are shown without their contents.
+fn is_symlink_loop(path: &Path) -> bool {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => path
+            .canonicalize()
+            .is_ok_and(|target| path.starts_with(target)),
+        _ => false,
+    }
+}
+
+fn is_skipped_mount(path: &Path) -> bool {
+    SKIPPED_MOUNTS
+        .get()
+        .is_some_and(|mounts| mounts.iter().any(|mount| mount == path))
+}
+
+fn is_timed_out(path: &Path) -> bool {
+    TIMED_OUT
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|timed_out| timed_out == path)
+}
+
+fn read_error(path: &Path) -> Option<String> {
+    READ_ERRORS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(failed, _)| failed == path)
+        .map(|(_, error)| error.clone())
+}
+
+// The entries of a directory with their types, sorted by path, or None if
+// it can't be read in time. A listing that is given up on is left to finish
+// or hang on its thread.
+fn list_dir(dirname: &Path, backend: StatBackend) -> Option<Vec<(PathBuf, NodeType)>> {
+    let on_network = NETWORK_MOUNTS
+        .get()
+        .is_some_and(|mounts| mounts.iter().any(|mount| dirname.starts_with(mount)));
+    let timeout = match READ_TIMEOUT.get() {
+        Some(timeout) if on_network => *timeout,
+        _ => return read_entries(dirname, backend),
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let path = dirname.to_path_buf();
+    thread::spawn(move || {
+        let _ = sender.send(read_entries(&path, backend));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(entries) => entries,
+        Err(_) => {
+            warn!(path = %display_path(dirname), ?timeout, "timed out reading directory");
+            TIMED_OUT.lock().unwrap().push(dirname.to_path_buf());
+            None
+        }
+    }
+}
+
+fn read_entries(dirname: &Path, backend: StatBackend) -> Option<Vec<(PathBuf, NodeType)>> {
+    let entries = match std::fs::read_dir(dirname) {
+        Ok(entries) => entries,
+        Err(e) => {
+            let mut errors = READ_ERRORS.lock().unwrap();
+            errors.retain(|(failed, _)| failed != dirname);
+            errors.push((dirname.to_path_buf(), e.to_string()));
+            return None;
+        }
+    };
+
+    // Entries that can't be read, such as protected system folders at the
+    // root of a drive, are skipped. The type of an entry usually comes with
+    // the directory listing, so only the others are handed to the backend.
+    let mut entries: Vec<(PathBuf, Option<NodeType>)> = entries
+        .filter_map(|entry| {
+            if entry.is_err() {
+                SCAN_ERRORS.fetch_add(1, Ordering::Relaxed);
+            }
+            entry.ok()
+        })
+        .map(|entry| (entry.path(), listed_type(&entry)))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let unknown: Vec<PathBuf> = entries
+        .iter()
+        .filter(|(_, node_type)| node_type.is_none())
+        .map(|(path, _)| path.clone())
+        .collect();
+    let mut dirs = backend.are_dirs(&unknown).into_iter();
+    let entries = entries
+        .into_iter()
+        .map(|(path, node_type)| {
+            let node_type = node_type.unwrap_or_else(|| match dirs.next() {
+                Some(true) => NodeType::Dir,
+                _ => NodeType::File,
+            });
+            (path, node_type)
+        })
+        .collect();
+    Some(entries)
+}
+
+// Symlinks are listed as the kind of entry they point to, which takes a
+// stat to find out, so their type isn't known from the listing.
+fn listed_type(entry: &std::fs::DirEntry) -> Option<NodeType> {
+    match entry.file_type() {
+        Ok(file_type) if file_type.is_dir() => Some(NodeType::Dir),
+        Ok(file_type) if !file_type.is_symlink() => Some(NodeType::File),
+        _ => None,
+    }
+}
+
+// Scans the whole tree in one pass. Resuming a scan lists the directories
+// on the way to where it stopped again, so nothing is gained by chunks when
+// there is nothing to draw in between.
+fn read_dir_complete(
+    root: &mut TreeNode,
+    dirname: PathBuf,
+    max_depth: Option<usize>,
+    backend: StatBackend,
+) {
+    let mut allocated = i32::MAX;
+    read_dir_incremental(root, dirname, &mut allocated, max_depth, backend);
+}
+
+// Reads a directory that timed out again, with what is left of the maximum
+// depth below it.
+fn read_again(
+    root: &mut TreeNode,
+    dirname: &Path,
+    path: &Path,
+    max_depth: Option<usize>,
+    backend: StatBackend,
+) {
+    let relative = match path.strip_prefix(dirname) {
+        Ok(relative) => relative,
+        Err(_) => return,
+    };
+    let mut node = root;
+    for component in relative.iter() {
+        let val = component.to_string_lossy();
+        node = match node.children.iter_mut().find(|child| child.val == val) {
+            Some(child) => child,
+            None => return,
+        };
+    }
+
+    TIMED_OUT
+        .lock()
+        .unwrap()
+        .retain(|timed_out| timed_out != path);
+    node.children.clear();
+    let depth = relative.iter().count();
+    read_dir_complete(
+        node,
+        path.to_path_buf(),
+        max_depth.map(|max_depth| max_depth.saturating_sub(depth)),
+        backend,
+    );
+}
+
+// Reads the contents of the directories `depth` levels down, which a scan
+// with that maximum depth left out.
+fn read_level(root: &mut TreeNode, dirname: &Path, depth: usize, backend: StatBackend) {
+    if depth == 0 {
+        if root.children.is_empty() {
+            read_dir_complete(root, dirname.to_path_buf(), Some(1), backend);
+        }
+        return;
+    }
+
+    for child in &mut root.children {
+        let path = dirname.join(&child.val);
+        read_level(child, &path, depth - 1, backend);
+    }
+}
+
 fn cli() -> Command {
     command!()
+        .after_long_help(
+            "Options can also be set in ~/.config/tree-rs/config, one `option = value` \
+             line each, which the first start in a terminal offers to write, or as \
+             TOML in ~/.config/tree-rs/config.toml.\n\n\
+             Entries are colored like ls colors them when LS_COLORS is set.\n\n\
+             Exit status: 0 when an entry was selected with Enter or the entries were \
+             listed, 1 when the TUI was quit, \
+             2 when entries couldn't be read and 3 for invalid arguments.",
+        )
         .group(ArgGroup::new("LISTING OPTIONS").multiple(true))
         .next_help_heading("LISTING OPTIONS")
-        .args([arg!(-d --depth <level> "Descend only level directories deep").group("LISTING OPTIONS")])
+        .args([arg!(-d --"max-depth" <level> "Descend only level directories deep; + and - change it in navigation mode")
+            .visible_alias("depth")
+            .value_parser(clap::value_parser!(usize))
+            .group("LISTING OPTIONS")])
         .args([arg!(-n --number <number> "Specify the number of items to return").group("LISTING OPTIONS")])
+        .args([arg!(-e --pattern <regex> "Initial search pattern").group("LISTING OPTIONS")])
+        .arg(
+            arg!(--match <mode> "Match patterns as regexes, fuzzy like fzf with the best matches first, or as globs; Ctrl+F switches")
+                .value_parser(MATCH_MODE_NAMES)
+                .default_value("regex"),
+        )
+        .arg(
+            arg!(--content "Match the pattern against the contents of files instead of names; Ctrl+L switches")
+                .long_help(
+                    "Match the pattern against the contents of files instead of their names, \
+                     like a tree-shaped grep, and show the first matching line of each \
+                     file. The first 64K of each file are searched and binary files are \
+                     skipped. Ctrl+L switches between contents and names.",
+                ),
+        )
+        .arg(
+            arg!(--todo "List only files with TODO, FIXME or HACK comments and count them")
+                .long_help(
+                    "List only files with TODO, FIXME or HACK comments, with a column \
+                     counting them. Without a pattern this searches contents for the \
+                     regex \\b(TODO|FIXME|HACK)\\b, which can be edited like any other.",
+                ),
+        )
+        .arg(
+            arg!(--tags <path> "Search the symbols in a tags file written by ctags; Ctrl+L switches to them")
+                .long_help(
+                    "Search the symbols in a tags file written by ctags. Ctrl+L switches \
+                     from names to contents and then to symbols, which are listed under \
+                     their files. Enter on a symbol opens $VISUAL or $EDITOR at its line, \
+                     or prints the file and the line when neither is set.",
+                ),
+        )
+        .arg(
+            arg!(--top <count> "Number of best matches the quick view lists; Ctrl+Q opens it")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(arg!(--"include-virtual" "Scan virtual filesystems such as /proc and /sys, which are only listed by default"))
+        .arg(arg!(--hidden "List hidden entries, whose names start with a dot; Ctrl+H toggles them"))
+        .arg(arg!(--"hide-binaries" "Leave out binary files, which have a null byte near the start; zb toggles this in navigation mode"))
+        .arg(arg!(--"show-macos-metadata" "List .DS_Store and ._ resource fork files"))
+        .arg(
+            arg!(--ignore <glob> "Leave out entries whose names match a glob, e.g. target or *.o")
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--case <mode> "Match patterns regardless of case or not; auto does as the filesystem of the directory does")
+                .value_parser(["auto", "sensitive", "insensitive"])
+                .default_value("auto"),
+        )
+        .arg(arg!(--"case-conflicts" "List only entries whose names differ from a sibling's only by case; gc toggles this in navigation mode"))
+        .arg(arg!(--lock "Warn when another instance started with --lock shows the same directory"))
+        .arg(
+            arg!(--"from-stdin" "Show the paths read from stdin, one per line, instead of scanning")
+                .long_help(
+                    "Show the paths read from stdin, one per line, instead of scanning, \
+                     e.g. `find . -name '*.rs' | tree-rs --from-stdin`. Relative paths are \
+                     taken from the current directory and paths outside of the directory \
+                     are left out. Keys are read from the terminal.",
+                ),
+        )
+        .arg(
+            arg!(--scaffold <file> "Create the layout a file describes under the directory, after showing it")
+                .long_help(
+                    "Create the layout a file describes under the directory, which has to \
+                     exist. The file is the output of --output json, or an outline with one \
+                     name per line indented below its directory, like a markdown list or \
+                     the drawing --print makes, where names ending in / are directories. \
+                     The TUI shows the layout with what is new marked, and Enter creates \
+                     it. Otherwise what is created is printed, + for new entries and ! for \
+                     entries in the way. Existing entries are left as they are.",
+                )
+                .conflicts_with_all(["from-stdin", "batch", "print", "output", "lint-names", "lint-headers"]),
+        )
+        .arg(
+            arg!(--"dry-run" "With --scaffold, only print what would be created")
+                .requires("scaffold"),
+        )
+        .arg(
+            arg!(--storage <kind> "Tune scanning for the storage the tree is on")
+                .value_parser(STORAGE_NAMES)
+                .default_value("auto"),
+        )
+        .arg(
+            arg!(--"read-timeout" <seconds> "Give up on directories on network mounts that can't be read in time, 0 to wait forever")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("10"),
+        )
+        .arg(
+            arg!(--backend <name> "How entries are stat'ed while scanning")
+                .value_parser(BACKEND_NAMES)
+                .default_value("auto"),
+        )
+        .arg(
+            arg!(--workers <count> "Threads computing columns, instead of the storage's default")
+                .value_parser(clap::value_parser!(usize)),
+        )
         .arg(arg!(<dirname> "Directory name").required(false))
+        // The `directory` setting of the config file, which the directory
+        // given on the command line replaces.
+        .arg(arg!(--directory <dirname> "Directory to list when none is given").hide(true))
+        .next_help_heading("DISPLAY OPTIONS")
+        .arg(arg!(--"depth-colors" "Tint entries by their depth in the tree"))
+        .arg(arg!(--reverse "Turn the order of entries around, e.g. the largest last with --sort size; S toggles this in navigation mode"))
+        .arg(arg!(--"dirs-first" "List directories before the files next to them, like tree --dirsfirst; zd toggles this in navigation mode"))
+        .arg(arg!(--compact "Show directories containing nothing but one directory as one entry, like a/b/c; zc toggles this in navigation mode"))
+        .arg(arg!(--"root-label" <text> "Show the root as text instead of its full path"))
+        .arg(arg!(--icons "Precede entries with an icon for their kind, which needs a Nerd Font"))
+        .arg(arg!(--zebra "Alternate the background of every other row"))
+        .arg(
+            arg!(--palette <name> "Colors of the interface")
+                .value_parser(PALETTE_NAMES)
+                .default_value("default"),
+        )
+        .arg(
+            arg!(--"selection-style" <style> "Highlight only the name or the full row")
+                .value_parser(["name", "bar"])
+                .default_value("name"),
+        )
+        .arg(
+            arg!(--"status-format" <format> "Layout of the status bar")
+                .long_help(
+                    "Layout of the status bar. The placeholders {pattern}, {matches}, \
+                     {files}, {dirs}, {scan}, {root}, {path}, {size} and {hints} are \
+                     replaced with their current values. {path} and {size} are those of \
+                     the selected entry; the size of a directory is shown once it has \
+                     been measured. {root} is the root as the tree shows it.",
+                )
+                .default_value(DEFAULT_STATUS_FORMAT),
+        )
+        .arg(
+            arg!(--"zero-match-alert" <alert> "Ring the bell or flash the status bar when nothing matches")
+                .value_parser(["none", "bell", "flash"])
+                .default_value("none"),
+        )
+        .arg(arg!(--size "Show sizes at the right edge, directories with everything in them; Ctrl+K toggles them"))
+        .arg(arg!(--git "Mark modified, staged, untracked and ignored entries in a git repository"))
+        .arg(arg!(--"regex-debug" "Show how the pattern matches the selected entry"))
+        .arg(arg!(--format <template> "Format matching entries with a template").long_help(FORMAT_HELP))
+        .arg(
+            arg!(--"time-format" <format> "Format of {mtime}: epoch, iso or a strftime pattern")
+                .long_help(TIME_FORMAT_HELP)
+                .default_value("epoch"),
+        )
+        .arg(
+            arg!(--column <name> "Show a column computed in the background")
+                .value_parser(PROVIDER_NAMES)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--sort <key> "Order entries by name, modification time, extension, type, or a column, largest first; s cycles in navigation mode")
+                .value_parser(SORT_NAMES.iter().chain(PROVIDER_NAMES.iter()).copied().collect::<Vec<_>>())
+                .default_value("name"),
+        )
+        .arg(
+            arg!(--budget <rule> "Highlight directories larger than a budget, e.g. target=2G or *=10G")
+                .long_help(
+                    "Highlight directories larger than a budget, e.g. target=2G, or *=10G \
+                     for all directories without a budget of their own. Sizes are taken \
+                     from the disk or size column, which is added if neither is shown. \
+                     Ctrl+B lists the directories over budget.",
+                )
+                .action(ArgAction::Append),
+        )
+        .arg(arg!(--"screen-reader" "Describe entries in words and drop box drawing, for screen readers"))
+        .arg(
+            arg!(--"fold-level" <level> "Start with directories deeper than level folded")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .next_help_heading("OUTPUT OPTIONS")
+        .arg(arg!(--batch "Print matching entries one per line instead of starting the TUI")
+                .long_help(
+                    "Print matching entries one per line instead of starting the TUI. \
+                     This is the default when the output isn't a terminal or TERM is dumb.",
+                ))
+        .arg(
+            arg!(-p --print "Print the matching entries as a tree, like tree(1), instead of starting the TUI")
+                .long_help(
+                    "Print the matching entries as a tree, like tree(1), instead of starting \
+                     the TUI. Directories leading to a match are kept. Entries are colored \
+                     by type, or by depth with --depth-colors, when the output is a terminal.",
+                )
+                .conflicts_with("batch"),
+        )
+        .arg(
+            arg!(--"lint-names" "Report names that can't be copied as they are to Windows or macOS, instead of starting the TUI")
+                .long_help(
+                    "Report names that can't be copied as they are to Windows or macOS, \
+                     instead of starting the TUI: characters Windows rejects, device names \
+                     such as CON, trailing spaces and dots, names over 255 and paths over \
+                     260 characters, and names with decomposed accents. Ctrl+N lists them \
+                     in the TUI.",
+                )
+                .conflicts_with_all(["batch", "print"]),
+        )
+        .arg(arg!(--watch "Keep directory sizes up to date as files change, on Linux"))
+        .arg(
+            arg!(--snapshot <file> "Show how directory sizes changed since the sizes saved in a file, next to the size columns")
+                .long_help(
+                    "Show how the size of each directory changed since the sizes saved in \
+                     a file, next to the size and disk columns, e.g. 1.2G ▲ +120M. Nothing \
+                     is shown while the file doesn't exist. --update-snapshot saves the \
+                     current sizes to it.",
+                ),
+        )
+        .arg(
+            arg!(--"update-snapshot" "Save the size of every directory to the --snapshot file, instead of starting the TUI")
+                .requires("snapshot")
+                .conflicts_with_all(["batch", "print", "output", "lint-names", "lint-headers", "scaffold"]),
+        )
+        .arg(
+            arg!(--"lint-headers" "Report source files without a license header, instead of starting the TUI")
+                .long_help(
+                    "Report source files without a license header, one per line relative \
+                     to the root, instead of starting the TUI. A header is a match of \
+                     --license-header in the first 20 lines. Source files are told by \
+                     their extension, such as .rs, .c or .py. The license column flags \
+                     them in the TUI.",
+                )
+                .conflicts_with_all(["batch", "print", "lint-names"]),
+        )
+        .arg(
+            arg!(--"license-header" <regex> "What a license header has to match, by default a copyright notice or SPDX identifier")
+                .default_value(DEFAULT_HEADER),
+        )
+        .arg(
+            arg!(--output <format> "Print the matching entries instead of starting the TUI: lines like --batch, tree like --print, json, mkdir-script, or files-from")
+                .long_help(
+                    "Print the matching entries instead of starting the TUI: lines like \
+                     --batch, tree like --print, json for one nested object per entry \
+                     with its path, type (file or dir), whether it matched the pattern \
+                     rather than leading to a match, the error reading it or null, and its \
+                     children, or mkdir-script for a shell script of mkdir -p and touch \
+                     commands that recreates the directories and empty files in the \
+                     current directory, or files-from for a list of the files relative to \
+                     the root, for rsync --files-from or tar -T. Directories are listed \
+                     when nothing in them is, and stand for all of their contents, so \
+                     rsync needs -r.",
+                )
+                .value_parser(["lines", "tree", "json", "mkdir-script", "files-from"])
+                .conflicts_with_all(["batch", "print", "lint-names", "lint-headers"]),
+        )
+        .arg(
+            arg!(--gitkeep "Have --output mkdir-script put a .gitkeep file in empty directories, so git keeps them")
+                .requires("output"),
+        )
+        .arg(arg!(-'0' --print0 "End the printed selection with a NUL instead of a line break, for xargs -0, rsync --from0 or tar --null"))
+        .arg(
+            arg!(--"list-file" <path> "Write the picked entries, or --output files-from, to a file as a list relative to the root")
+                .long_help(
+                    "Write the entries picked in the TUI, or the list of --output \
+                     files-from, to a file instead of printing them. Paths are relative to \
+                     the root, so the file can be handed to rsync --files-from or tar -T \
+                     with the root as the source directory.",
+                ),
+        )
+        .arg(arg!(--"emit-events" "Write scan, match and selection events to stderr as JSON lines"))
+        .arg(arg!(--"debug-log" <path> "Log scans, keys and render timings to a file"))
+        .arg(
+            arg!(--screenshot <path> "Where Ctrl+S saves the screen: .svg, .html or text with ANSI colors")
+                .long_help(
+                    "Where Ctrl+S saves the screen as it is drawn. Files ending in .svg or \
+                     .html get an SVG image or an HTML page, anything else gets text with \
+                     ANSI colors. By default the screen is saved to tree-rs-<time>.ans in \
+                     the current directory.",
+                ),
+        )
+        .next_help_heading("INPUT OPTIONS")
+        .arg(
+            arg!(--bind <chord> "Bind a key sequence to an action in navigation mode, e.g. gg=top, or a key in angle brackets in any mode, e.g. <Ctrl+x>=quit")
+                .long_help(format!(
+                    "Bind a key sequence to an action in navigation mode, e.g. gg=top. \
+                     A key in angle brackets is bound in any mode instead, ahead of what \
+                     it does by default, e.g. <Ctrl+x>=quit or <PageDown>=down, and \
+                     binding it to none turns it off. Keys are characters or Esc, Enter, \
+                     Tab, Backspace, Delete, Home, End, PageUp, PageDown, Up, Down, \
+                     Left, Right, Space and F1 to F12, after any of Ctrl+, Alt+ and \
+                     Shift+. The actions are {}.",
+                    ACTION_NAMES.join(", ")
+                ))
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--vim "Start in navigation mode, like vim's normal mode; / searches and Esc goes back")
+                .long_help(
+                    "Start in navigation mode, which is like vim's normal mode: j/k move, \
+                     h/l fold and unfold, gg/G go to the top and bottom, and / switches to \
+                     typing the pattern. Esc goes back to navigation mode, and there it \
+                     clears the pattern and then quits.",
+                ),
+        )
+        .arg(
+            arg!(--"page-size" <rows> "Rows PageUp/PageDown move, instead of the window height; Ctrl+D/Ctrl+U move half")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(arg!(--"no-mouse" "Leave the mouse to the terminal so text can be selected and copied, instead of clicking and scrolling the tree"))
+        .arg(
+            arg!(--tick <ms> "How long to wait for a key before drawing background updates")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("10"),
+        )
+        .arg(arg!(--"key-debug" "Show the keys as the terminal reports them, also toggled with F12"))
+        .next_help_heading("SCRIPTING OPTIONS")
+        .arg(arg!(--script <path> "Rhai script defining column, filter and on_select functions"))
+        .next_help_heading("CONFIG OPTIONS")
+        .arg(
+            arg!(--config <path> "Read the settings from this file instead of ~/.config/tree-rs/config.toml or config")
+                .long_help(
+                    "Read the settings from this file instead of ~/.config/tree-rs/config.toml, \
+                     or ~/.config/tree-rs/config when there is no config.toml. Files ending \
+                     in .toml are read as TOML, others as option = value lines. Options \
+                     given on the command line override the settings.",
+                ),
+        )
+        .arg(
+            arg!(--profile <name> "Apply the settings of a profile in the config file")
+                .long_help(
+                    "Apply the settings of a profile in the config file, the lines after \
+                     a [name] header, on top of the ones before the first header.",
+                ),
+        )
 }
 
-fn ui(f: &mut Frame<impl Backend>, search_term: Option<String>, content: Option<String>) {
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+
+fn ui(
+    f: &mut Frame<impl Backend>,
+    status: Spans,
+    state: &State,
+    search: &Search,
+    options: &DisplayOptions,
+) {
+    // Below this size the panes don't fit. The layout is restored as soon
+    // as the terminal is resized.
+    if f.size().width < MIN_WIDTH || f.size().height < MIN_HEIGHT {
+        state.viewport.set((0, 0));
+        state.window_rows.set(0);
+        let message = Paragraph::new(trf("too_small", &[&MIN_WIDTH, &MIN_HEIGHT]))
+            .wrap(tui::widgets::Wrap { trim: true });
+        f.render_widget(message, f.size());
+        return;
+    }
+
+    let search_window_height = if search.error.is_some() { 4 } else { 3 };
+
     let mut main_window_size = f.size();
-    main_window_size.height -= 3;
+    main_window_size.height -= search_window_height;
 
     let search_window_size = Rect::new(
         main_window_size.x,
         main_window_size.y + main_window_size.height,
         main_window_size.width,
-        3,
+        search_window_height,
     );
 
-    let tree_window = Block::default().title("Tree").borders(Borders::ALL);
-    let search_window = Block::default().title("Search").borders(Borders::ALL);
+    let debug_text = match state.lines.get(state.selected) {
+        Some(line) if state.regex_debug => {
+            Some(describe_match(&search.filter, &line.name, &state.palette))
+        }
+        _ => None,
+    };
+    let debug_window_height = debug_text.as_ref().map_or(0, |text| {
+        (text.len() as u16 + 2).min(main_window_size.height / 2)
+    });
+    main_window_size.height -= debug_window_height;
+
+    let details_text = match state.lines.get(state.selected) {
+        Some(line) if state.details => Some(details(&line.path, &options.time_format)),
+        _ => None,
+    };
+    let details_window_height = details_text.as_ref().map_or(0, |text| {
+        (text.len() as u16 + 2).min(main_window_size.height / 2)
+    });
+    main_window_size.height -= details_window_height;
+
+    // Screen readers would read out the box drawing, so it is left out.
+    let borders = if options.screen_reader {
+        Borders::NONE
+    } else {
+        Borders::ALL
+    };
+
+    let tree_window = Block::default().title(tr("tree")).borders(borders);
+    let mode = match state.macros.recording() {
+        Some(register) => trf("recording", &[&register]),
+        None if state.navigating => tr("navigate"),
+        None => tr("search"),
+    };
+    let search_window =
+        if search.matches == 0 && !state.search_term.is_empty() && search.error.is_none() {
+            Block::default()
+                .title(Spans::from(vec![
+                    Span::raw(format!("{} — ", mode)),
+                    Span::styled(
+                        tr("zero_matches"),
+                        Style::default()
+                            .fg(state.palette.error)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                ]))
+                .borders(borders)
+        } else if let Some(message) = &state.message {
+            Block::default()
+                .title(format!("{} — {}", mode, message))
+                .borders(borders)
+        } else if state.scanning {
+            Block::default()
+                .title(format!("{} — {}", mode, trf("scanning", &[&state.scanned])))
+                .borders(borders)
+        } else {
+            Block::default().title(mode.as_str()).borders(borders)
+        };
     let mut text = Vec::new();
 
-    match content {
-        Some(c) => {
-            c.split("\n").for_each(|line| {
-                text.push(Spans::from(vec![Span::raw(format!("{}", line))]));
-            });
+    // Keep the selected line within the window. Only the visible rows are
+    // rendered, since columns may be expensive to compute.
+    let tree_height = main_window_size.height.saturating_sub(2) as usize;
+    let scroll = (state.selected + 1).saturating_sub(tree_height);
+    state.window_rows.set(tree_height);
+    state.viewport.set((
+        scroll,
+        tree_height.min(state.lines.len().saturating_sub(scroll)),
+    ));
+
+    let row_width = main_window_size.width.saturating_sub(2) as usize;
+    for (i, line) in state
+        .lines
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(tree_height)
+    {
+        let mut style = match options.color {
+            ColorOptions::Depth => {
+                Style::default().fg(Color::Indexed(state.palette.depth_color(line.depth)))
+            }
+            _ => Style::default(),
+        };
+
+        // The root is the scan directory, not a match.
+        let name = match &options.format {
+            Some(template) if line.depth > 0 && search.filter.is_match(&line.name) => {
+                let info = NodeInfo {
+                    path: &line.path,
+                    name: &line.name,
+                    depth: line.depth,
+                    node_type: line.node_type,
+                };
+                format_node(template, &search.filter, &options.time_format, &info)
+            }
+            _ => line.name.clone(),
+        };
+        let name = if line.depth == 0 && state.view_root.is_empty() {
+            options.root_label.clone()
+        } else {
+            name
+        };
+        let (prefix, name) = if options.screen_reader {
+            (String::new(), spoken_line(line, &name))
+        } else if options.icons && line.tag.is_none() {
+            let icon = icon(&line.path, line.node_type);
+            (line.prefix.clone(), format!("{} {}", icon, name))
+        } else {
+            (line.prefix.clone(), name)
+        };
+
+        let annotation = state.annotations.get(&line.path);
+        let mut column = if line.folded > 0 {
+            format!(" [+{}]", line.folded)
+        } else {
+            String::new()
+        };
+        if let Some(found) = state.content.get(&line.path).filter(|_| state.content_mode) {
+            column.push_str(&format!("  {}", found.display()));
+        }
+        if state.case_conflicts.contains(&line.path) {
+            column.push_str(&format!(" {}", tr("case_conflict")));
+        }
+        if line.node_type == NodeType::Dir && is_timed_out(&line.path) {
+            column.push_str(&format!(" {}", tr("timed_out")));
+        }
+        if let Some(text) = annotation.and_then(|a| a.text.as_ref()) {
+            column.push_str(&format!("  {}", text));
+        }
+        if let Some(script) = &state.script {
+            if let Some(text) = script.column(&line.path, &line.name, line.node_type) {
+                column.push_str(&format!("  {}", text));
+            }
+        }
+        let size_index = state.annotator.index_of("size");
+        column.push_str(&state.annotator.column(&line.path, size_index));
+        let size = match size_index {
+            Some(index) if state.show_size => state
+                .annotator
+                .cell(index, &line.path)
+                .map_or(String::new(), |size| format!(" {}", size)),
+            _ => String::new(),
+        };
+
+        let mark = if state.marked.contains(&line.path) {
+            "* "
+        } else {
+            ""
+        };
+
+        let git = state.git.as_ref().and_then(|git| git.get(&line.path));
+        let marker = git.map_or(String::new(), |status| format!(" {}", status.marker()));
+
+        let selected = i == state.selected;
+        let bar = options.zebra && i % 2 == 1 || selected && options.selection_style.is_bar();
+
+        // Pad the row so sizes line up at the right edge and stripes and the
+        // selection bar span the whole window.
+        let padding = if bar || !size.is_empty() {
+            let width = prefix.chars().count()
+                + mark.chars().count()
+                + name.chars().count()
+                + marker.chars().count()
+                + column.chars().count()
+                + size.chars().count();
+            " ".repeat(row_width.saturating_sub(width))
+        } else {
+            String::new()
+        };
+
+        if options.zebra && i % 2 == 1 {
+            style = style.bg(state.palette.zebra);
+        }
+
+        let mut name_style = style;
+        if let (ColorOptions::NoColor, Some(ls_colors)) = (&options.color, &options.ls_colors) {
+            if let Some(ls_style) = ls_colors.style(&line.path) {
+                name_style = name_style.patch(ls_style);
+            }
+        }
+        if let Some(color) = annotation.and_then(|a| a.color) {
+            name_style = name_style.fg(color);
         }
-        None => {}
+        if over_budget(state, options, line).is_some() {
+            name_style = name_style
+                .fg(state.palette.error)
+                .add_modifier(Modifier::BOLD);
+        }
+        if selected {
+            name_style = name_style.add_modifier(Modifier::REVERSED);
+            if options.selection_style.is_bar() {
+                style = name_style;
+            }
+        }
+
+        // In jump mode every row is preceded by its label. Labels that no
+        // longer match what has been typed are blanked out.
+        let label = match &state.jump {
+            Some(typed) => {
+                let label = jump_label(i - scroll);
+                if label.starts_with(typed.as_str()) {
+                    Span::styled(
+                        format!("{} ", label),
+                        Style::default()
+                            .fg(state.palette.jump_label)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    Span::raw("   ")
+                }
+            }
+            None => Span::raw(""),
+        };
+
+        // Fuzzy matches underline the characters that matched.
+        let highlighted = match state.match_mode {
+            MatchMode::Fuzzy if !state.search_term.is_empty() => {
+                fuzzy_positions(&state.search_term, &line.name, options.case_insensitive)
+            }
+            _ => None,
+        };
+        let name = match highlighted {
+            Some(positions) if name.ends_with(&line.name) => {
+                let highlight = name_style
+                    .fg(state.palette.highlight)
+                    .add_modifier(Modifier::UNDERLINED);
+                let offset = name.chars().count() - line.name.chars().count();
+                highlight_chars(&name, &positions, offset, name_style, highlight)
+            }
+            _ => vec![Span::styled(name, name_style)],
+        };
+
+        let mut spans = vec![
+            label,
+            Span::styled(prefix, style),
+            Span::styled(
+                mark,
+                style
+                    .fg(state.palette.highlight)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ];
+        spans.extend(name);
+        spans.extend([
+            Span::styled(marker, style.fg(git_color(git, &state.palette))),
+            Span::styled(column, style.fg(state.palette.dim)),
+            Span::styled(padding, style),
+            Span::styled(size, style),
+        ]);
+        text.push(Spans::from(spans));
     }
 
-    let tree_widget = Paragraph::new(text)
-        .block(tree_window)
-        .wrap(tui::widgets::Wrap { trim: false });
+    let tree_widget = Paragraph::new(text).block(tree_window);
+
+    let status_style = if state.flash {
+        Style::default()
+            .bg(state.palette.flash)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+
+    let mut status_text = vec![status];
+    if let Some(e) = &search.error {
+        status_text.push(Spans::from(Span::styled(
+            e.display(),
+            Style::default()
+                .fg(state.palette.error)
+                .add_modifier(Modifier::BOLD),
+        )));
+    }
 
-    let search_widget = Paragraph::new(search_term.unwrap_or("".to_string()))
+    let search_widget = Paragraph::new(status_text)
+        .style(status_style)
         .block(search_window)
         .wrap(tui::widgets::Wrap { trim: false });
 
     f.render_widget(tree_widget, main_window_size);
+
+    // Screen readers follow the cursor, so it is kept on the selected entry.
+    // A window too small for any rows leaves it where it is.
+    if options.screen_reader && !state.lines.is_empty() && tree_height > 0 {
+        let x = main_window_size.x;
+        let y = main_window_size.y + 1 + (state.selected - scroll) as u16;
+        f.set_cursor(x, y);
+    }
     f.render_widget(search_widget, search_window_size);
+
+    if let Some(details_text) = details_text {
+        let details_window_size = Rect::new(
+            main_window_size.x,
+            main_window_size.y + main_window_size.height,
+            main_window_size.width,
+            details_window_height,
+        );
+        let details_widget = Paragraph::new(details_text)
+            .block(Block::default().title(tr("details")).borders(borders));
+        f.render_widget(details_widget, details_window_size);
+    }
+
+    if let Some(debug_text) = debug_text {
+        let debug_window_size = Rect::new(
+            main_window_size.x,
+            main_window_size.y + main_window_size.height + details_window_height,
+            main_window_size.width,
+            debug_window_height,
+        );
+        let debug_widget =
+            Paragraph::new(debug_text).block(Block::default().title(tr("regex")).borders(borders));
+        f.render_widget(debug_widget, debug_window_size);
+    }
+
+    if let Some(projects) = &state.projects {
+        project_overlay(f, projects, main_window_size, &options.selection_style);
+    }
+
+    if let Some(recent) = &state.recent {
+        recent_overlay(
+            f,
+            recent,
+            &state.dirname,
+            main_window_size,
+            &options.selection_style,
+        );
+    }
+
+    if let Some(quick) = &state.quick {
+        quick_overlay(
+            f,
+            quick,
+            &state.dirname,
+            options.top,
+            main_window_size,
+            &options.selection_style,
+        );
+    }
+
+    if let Some(report) = &state.portability {
+        portability_overlay(
+            f,
+            report,
+            &state.dirname,
+            main_window_size,
+            &options.selection_style,
+        );
+    }
+
+    if state.budget_report {
+        budget_overlay(f, state, options, main_window_size);
+    }
+
+    if let Some(picker) = &state.themes {
+        theme_overlay(f, picker, main_window_size, &options.selection_style);
+    }
+
+    if let Some(prompt) = &state.prompt {
+        prompt_overlay(f, prompt, main_window_size);
+    }
+
+    if let Some(log) = &state.key_log {
+        key_overlay(f, log, main_window_size);
+    }
+}
+
+// Splits text into spans, styling the characters at the given positions,
+// counted from `offset`, with `highlight`.
+fn highlight_chars(
+    text: &str,
+    positions: &[usize],
+    offset: usize,
+    style: Style,
+    highlight: Style,
+) -> Vec<Span<'static>> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut highlighted = false;
+    for (i, c) in text.chars().enumerate() {
+        let is_match = i >= offset && positions.contains(&(i - offset));
+        if is_match != highlighted && !run.is_empty() {
+            let style = if highlighted { highlight } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), style));
+        }
+        highlighted = is_match;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(
+            run,
+            if highlighted { highlight } else { style },
+        ));
+    }
+    spans
+}
+
+// The color of the marker of a git status.
+fn git_color(status: Option<GitStatus>, palette: &Palette) -> Color {
+    match status {
+        Some(GitStatus::Staged) => palette.git_staged,
+        Some(GitStatus::Modified) => palette.git_modified,
+        Some(GitStatus::Untracked) => palette.git_untracked,
+        Some(GitStatus::Ignored) | None => palette.dim,
+    }
+}
+
+// Lists the last keys in the bottom right corner of the tree window.
+fn key_overlay(f: &mut Frame<impl Backend>, log: &VecDeque<String>, area: Rect) {
+    let width = (area.width / 2).max(24).min(area.width);
+    let height = (KEY_LOG_LENGTH as u16 + 2).min(area.height);
+    let overlay_size = Rect::new(
+        area.x + area.width - width,
+        area.y + area.height - height,
+        width,
+        height,
+    );
+
+    let items: Vec<ListItem> = log.iter().map(|key| ListItem::new(key.as_str())).collect();
+    let list = List::new(items).block(Block::default().title(tr("keys")).borders(Borders::ALL));
+
+    f.render_widget(Clear, overlay_size);
+    f.render_widget(list, overlay_size);
+}
+
+// Describes an entry in words for screen readers, e.g. "src, directory,
+// level 2, 14 children".
+fn spoken_line(line: &Line, name: &str) -> String {
+    match line.node_type {
+        NodeType::File => trf("spoken_file", &[&name, &line.depth]),
+        NodeType::Dir => {
+            let children = match line.children {
+                1 => tr("one_child"),
+                n => trf("children", &[&n]),
+            };
+            let key = if line.folded > 0 {
+                "spoken_folded_dir"
+            } else {
+                "spoken_dir"
+            };
+            trf(key, &[&name, &line.depth, &children])
+        }
+    }
+}
+
+fn project_overlay(
+    f: &mut Frame<impl Backend>,
+    projects: &JumpList<Project>,
+    area: Rect,
+    selection_style: &SelectionStyle,
+) {
+    let overlay_size = centered(area, area.width * 3 / 4, projects.entries.len() as u16 + 2);
+    list_overlay(
+        f,
+        projects,
+        &Project::display,
+        tr("projects"),
+        "",
+        overlay_size,
+        selection_style,
+    );
+}
+
+// A box of the given size in the middle of an area, or as much of it as
+// fits.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// Draws a jump list over the tree with its selected entry highlighted, or
+// `empty` when it has no entries.
+fn list_overlay<T>(
+    f: &mut Frame<impl Backend>,
+    list: &JumpList<T>,
+    display: &dyn Fn(&T) -> String,
+    title: String,
+    empty: &str,
+    overlay_size: Rect,
+    selection_style: &SelectionStyle,
+) {
+    let reversed = Style::default().add_modifier(Modifier::REVERSED);
+
+    let items: Vec<ListItem> = if list.entries.is_empty() {
+        vec![ListItem::new(empty.to_string())]
+    } else {
+        list.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| match selection_style {
+                SelectionStyle::Name if i == list.selected => {
+                    ListItem::new(Span::styled(display(entry), reversed))
+                }
+                _ => ListItem::new(display(entry)),
+            })
+            .collect()
+    };
+
+    let mut widget = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+    if let SelectionStyle::Bar = selection_style {
+        widget = widget.highlight_style(reversed);
+    }
+
+    let mut state = ListState::default();
+    state.select(Some(list.selected));
+
+    f.render_widget(Clear, overlay_size);
+    f.render_stateful_widget(widget, overlay_size, &mut state);
+}
+
+// Lists the entries picked in earlier sessions, or says that there are
+// none.
+fn recent_overlay(
+    f: &mut Frame<impl Backend>,
+    recent: &JumpList<PathBuf>,
+    root: &Path,
+    area: Rect,
+    selection_style: &SelectionStyle,
+) {
+    let overlay_size = centered(
+        area,
+        area.width * 3 / 4,
+        recent.entries.len().max(1) as u16 + 2,
+    );
+    list_overlay(
+        f,
+        recent,
+        &|entry| display_path(entry.strip_prefix(root).unwrap_or(entry)),
+        tr("recent"),
+        &tr("no_recent"),
+        overlay_size,
+        selection_style,
+    );
+}
+
+// Lists the best matches, or says that there are none.
+fn quick_overlay(
+    f: &mut Frame<impl Backend>,
+    quick: &JumpList<PathBuf>,
+    root: &Path,
+    top: usize,
+    area: Rect,
+    selection_style: &SelectionStyle,
+) {
+    let overlay_size = centered(
+        area,
+        area.width * 3 / 4,
+        quick.entries.len().max(1) as u16 + 2,
+    );
+    list_overlay(
+        f,
+        quick,
+        &|entry| display_path(entry.strip_prefix(root).unwrap_or(entry)),
+        trf("quick_view", &[&top]),
+        &tr("no_quick_matches"),
+        overlay_size,
+        selection_style,
+    );
+}
+
+// Lists the names that aren't portable, or says that there are none.
+fn portability_overlay(
+    f: &mut Frame<impl Backend>,
+    report: &JumpList<Finding>,
+    root: &Path,
+    area: Rect,
+    selection_style: &SelectionStyle,
+) {
+    let overlay_size = centered(
+        area,
+        area.width * 3 / 4,
+        report.entries.len().max(1) as u16 + 2,
+    );
+    list_overlay(
+        f,
+        report,
+        &|finding| finding.display(root),
+        tr("portability"),
+        &tr("portable_names"),
+        overlay_size,
+        selection_style,
+    );
+}
+
+// The size of a directory and its budget, if it is over it.
+fn over_budget(state: &State, options: &DisplayOptions, line: &Line) -> Option<(u64, u64)> {
+    if line.node_type != NodeType::Dir {
+        return None;
+    }
+    let limit = limit(&options.budgets, &line.name)?;
+    let index = state
+        .annotator
+        .index_of("disk")
+        .or_else(|| state.annotator.index_of("size"))?;
+    let size = state
+        .annotator
+        .value(index, &line.path)
+        .and_then(leading_number)?;
+    (size > limit).then_some((size, limit))
+}
+
+// Lists the directories over budget, the furthest over first.
+fn budget_overlay(
+    f: &mut Frame<impl Backend>,
+    state: &State,
+    options: &DisplayOptions,
+    area: Rect,
+) {
+    let mut offenders: Vec<(&Line, u64, u64)> = state
+        .lines
+        .iter()
+        .filter_map(|line| {
+            over_budget(state, options, line).map(|(size, limit)| (line, size, limit))
+        })
+        .collect();
+    offenders.sort_by(|a, b| (b.1 as f64 / b.2 as f64).total_cmp(&(a.1 as f64 / a.2 as f64)));
+
+    let items: Vec<ListItem> = if offenders.is_empty() {
+        vec![ListItem::new(tr("within_budget"))]
+    } else {
+        offenders
+            .iter()
+            .map(|(line, size, limit)| {
+                let path = line.path.strip_prefix(&state.dirname).unwrap_or(&line.path);
+                let path = match display_path(path) {
+                    path if path.is_empty() => ".".to_string(),
+                    path => path,
+                };
+                ListItem::new(trf(
+                    "over_budget",
+                    &[&path, &human_size(*size), &human_size(*limit)],
+                ))
+            })
+            .collect()
+    };
+
+    let width = area.width * 3 / 4;
+    let height = (items.len() as u16 + 2).min(area.height);
+    let overlay_size = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let list = List::new(items).block(Block::default().title(tr("budgets")).borders(Borders::ALL));
+
+    f.render_widget(Clear, overlay_size);
+    f.render_widget(list, overlay_size);
+}
+
+// Asks for a name or a confirmation, with the cursor after the text.
+fn prompt_overlay(f: &mut Frame<impl Backend>, prompt: &Prompt, area: Rect) {
+    let width = (area.width / 2).max(40).min(area.width);
+    let height = 3.min(area.height);
+    let overlay_size = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let text = prompt.text();
+    let paragraph = Paragraph::new(text.as_str())
+        .block(Block::default().title(prompt.title()).borders(Borders::ALL));
+
+    f.render_widget(Clear, overlay_size);
+    f.render_widget(paragraph, overlay_size);
+    let cursor = (text.chars().count() as u16 + 1).min(width.saturating_sub(2));
+    f.set_cursor(overlay_size.x + cursor, overlay_size.y + 1);
+}
+
+// Lists the palettes in the middle of the tree window, which stays visible
+// around it to preview the selected one.
+fn theme_overlay(
+    f: &mut Frame<impl Backend>,
+    picker: &ThemePicker,
+    area: Rect,
+    selection_style: &SelectionStyle,
+) {
+    let width = (area.width / 2).max(24);
+    let overlay_size = centered(area, width, picker.list.entries.len() as u16 + 2);
+    list_overlay(
+        f,
+        &picker.list,
+        &|name| name.to_string(),
+        tr("themes"),
+        "",
+        overlay_size,
+        selection_style,
+    );
 }
 
 fn refresh(
     root: &TreeNode,
-    search_term: String,
+    state: &mut State,
+    options: &DisplayOptions,
     terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-) {
-    let tree = filter_tree(&root, &search_term);
-    let content = print_tree(&tree, &Vec::new(), &ColorOptions::NoColor);
-    terminal
-        .draw(|f| ui(f, Some(search_term.clone()), Some(content.clone())))
+) -> usize {
+    let started = Instant::now();
+    let root = view(root, &state.view_root);
+
+    // An invalid pattern leaves the tree unfiltered while the error is shown.
+    let compiled = compile_pattern(
+        &state.search_term,
+        state.match_mode,
+        options.case_insensitive,
+    );
+    let (filter, error) = match compiled {
+        Ok(filter) => (filter, None),
+        Err(e) => (Regex::new("").unwrap(), Some(e)),
+    };
+
+    let path = view_path(&state.dirname, &state.view_root);
+    let hidden = &state.hidden;
+    let script = state.script.as_ref().filter(|script| script.has_filter());
+    // Files are hidden as binary once the annotator found them to be, and
+    // listed until then.
+    let binary = state
+        .annotator
+        .index_of("binary")
+        .filter(|_| state.hide_binaries);
+    let is_binary =
+        |path: &Path| binary.is_some_and(|index| state.annotator.value(index, path).is_some());
+    let visible = prune_tree(root, &path, &|path, node| {
+        !hidden.contains(path)
+            && (state.show_hidden || !is_dotfile(&node.val))
+            && (options.show_macos_metadata || !is_macos_metadata(&node.val))
+            && (node.node_type != NodeType::File || !is_binary(path))
+            && !options.ignore.iter().any(|glob| glob.is_match(&node.val))
+            && script.is_none_or(|script| script.keep(path, &node.val, node.node_type))
+    });
+    state.case_conflicts.clear();
+    case_conflicts(root, &path, &mut state.case_conflicts);
+    let visible = if state.only_case_conflicts {
+        only_case_conflicts(&visible, &path, &state.case_conflicts)
+    } else {
+        visible
+    };
+    let content = state.content_mode && !state.search_term.is_empty() && error.is_none();
+    let symbols = state.symbol_mode && !state.search_term.is_empty() && error.is_none();
+    let tags = options.tags.as_ref().filter(|_| symbols);
+    let by_path = matches_paths(&state.search_term, state.match_mode);
+    let dirname = state.dirname.clone();
+    let path_matched = |path: &Path| filter.is_match(&relative_path(&dirname, path));
+    let mut tree = if let Some(tags) = tags {
+        content_tree(&visible, &path, &|path| {
+            !tags.matching(path, &filter).is_empty()
+        })
+    } else if content {
+        state.content.set_pattern(&state.search_term, &filter);
+        state.content.request(&visible, &path);
+        let search = &state.content;
+        content_tree(&visible, &path, &|path| search.get(path).is_some())
+    } else if by_path {
+        filter_tree_by_path(&visible, &path, &path_matched)
+    } else {
+        filter_tree(&visible, &filter)
+    };
+
+    // Fuzzy matches are ordered by how well they match, like in fzf, and
+    // matches that are otherwise equal by how often and lately they were
+    // picked.
+    let frecency = |path: &Path| state.frecency.get(path).copied();
+    let fuzzy = state.match_mode == MatchMode::Fuzzy && !state.search_term.is_empty();
+    let pattern = &state.search_term;
+    if fuzzy {
+        sort_tree_by_score(&mut tree, &path, &|path, name| {
+            fuzzy_score(pattern, name, options.case_insensitive)
+                .map(|score| (score, frecency(path)))
+        });
+    } else if state.sort != SortKey::Name {
+        let annotator = &state.annotator;
+        sort_tree_by_setting(
+            &mut tree,
+            &path,
+            &state.sort,
+            &|name, path, _| {
+                let index = annotator.index_of(name)?;
+                annotator.value(index, path).and_then(leading_number)
+            },
+            &mut state.mtimes,
+        );
+    } else if !pattern.is_empty() {
+        sort_tree_by_score(&mut tree, &path, &|path, _| frecency(path));
+    }
+
+    if state.quick.is_some() {
+        let search = &state.content;
+        let entries = best_matches(
+            &tree,
+            &path,
+            options.top,
+            &|path, node| {
+                if let Some(tags) = tags {
+                    !tags.matching(path, &filter).is_empty()
+                } else if content {
+                    search.get(path).is_some()
+                } else if by_path {
+                    path_matched(path)
+                } else {
+                    filter.is_match(&node.val)
+                }
+            },
+            &|path, name| {
+                let score = fuzzy
+                    .then(|| fuzzy_score(pattern, name, options.case_insensitive))
+                    .flatten();
+                Some((score, frecency(path)))
+            },
+        );
+        if let Some(quick) = state.quick.as_mut() {
+            quick.set_entries(entries);
+        }
+    }
+
+    order_tree(&mut tree, &path, state.reverse, state.dirs_first);
+    if state.compact {
+        tree = compact_tree(&tree);
+    }
+
+    let mut lines = Vec::new();
+    flatten_tree(
+        &tree,
+        &path,
+        &[],
+        state.fold_level,
+        &state.toggled,
+        &mut lines,
+    );
+    if let Some(tags) = tags {
+        lines = add_symbols(lines, tags, &filter);
+    }
+
+    // Rescans, filters and values arriving in the background all change the
+    // rows, so the selection follows the entry the user picked. If that entry
+    // is gone, its nearest surviving ancestor is selected instead.
+    let selected = state.anchor.as_ref().and_then(|anchor| {
+        anchor
+            .ancestors()
+            .find_map(|candidate| lines.iter().position(|line| line.path == candidate))
+    });
+    state.lines = lines;
+    state.select(selected.unwrap_or(state.selected));
+
+    let search = Search {
+        matches: if let Some(tags) = tags {
+            tags.count(&filter)
+        } else if content {
+            state.content.count()
+        } else if by_path {
+            count_path_matches(root, &path, &path_matched)
+        } else {
+            count_matches(root, &filter)
+        },
+        filter,
+        error,
+    };
+
+    let selected = state.lines.get(state.selected);
+    let selected_path = selected.map(|line| display_path(&line.path));
+    let selected_size = selected.and_then(|line| match line.node_type {
+        NodeType::File => std::fs::symlink_metadata(&line.path)
+            .ok()
+            .map(|metadata| human_size(metadata.len())),
+        NodeType::Dir => state
+            .annotator
+            .index_of("size")
+            .and_then(|index| state.annotator.value(index, &line.path))
+            .map(str::to_string),
+    });
+    let status = format_status(
+        &options.status_format,
+        &StatusInfo {
+            pattern: &state.search_term,
+            matches: search.matches,
+            files: get_tree_count(root, NodeType::File),
+            dirs: get_tree_count(root, NodeType::Dir),
+            scanning: state.scanning,
+            root: &options.root_label,
+            path: selected_path.as_deref(),
+            size: selected_size,
+            error_position: search.error.as_ref().and_then(|e| e.position),
+        },
+        &state.palette,
+    );
+
+    let built = started.elapsed();
+    let frame = terminal
+        .draw(|f| ui(f, status.clone(), state, &search, options))
         .unwrap();
+    state.frame = frame.buffer.clone();
+    debug!(
+        rows = state.lines.len(),
+        build = ?built,
+        draw = ?(started.elapsed() - built),
+        "rendered"
+    );
+
+    // Columns are only computed for the rows on screen, unless the rows are
+    // sorted by one. Values arriving later trigger another refresh. Sizes
+    // are left out while they are toggled off, unless something needs them.
+    if !state.annotator.is_empty() {
+        let (scroll, rows) = match state.sort {
+            SortKey::Column(_) => (0, state.lines.len()),
+            _ => state.viewport.get(),
+        };
+        let needs_size = matches!(&state.sort, SortKey::Column(column) if column == "size")
+            || !options.budgets.is_empty() && state.annotator.index_of("disk").is_none();
+        let skip = state
+            .annotator
+            .index_of("size")
+            .filter(|_| !state.show_size && !needs_size);
+        for line in state.lines.iter().skip(scroll).take(rows) {
+            state.annotator.request(&line.path, line.node_type, skip);
+        }
+
+        // Every file is looked at while binary files are hidden.
+        if let Some(index) = state
+            .annotator
+            .index_of("binary")
+            .filter(|_| state.hide_binaries)
+        {
+            for line in &state.lines {
+                if line.node_type == NodeType::File {
+                    state
+                        .annotator
+                        .request_value(index, &line.path, line.node_type);
+                }
+            }
+        }
+
+        // Every directory is measured to find the ones over budget.
+        if !options.budgets.is_empty() {
+            for line in &state.lines {
+                if line.node_type == NodeType::Dir {
+                    state.annotator.request(&line.path, line.node_type, skip);
+                }
+            }
+        }
+    }
+
+    search.matches
+}
+
+// Opens $VISUAL or $EDITOR at a line of a file, with the `+line` argument
+// that vi, emacs, nano and most other editors take. Without either the file
+// and the line are printed, like grep -n prints them.
+fn open_editor(path: &Path, line: Option<usize>) -> ExitCode {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(|editor| editor.to_string_lossy().to_string())
+        .find(|editor| !editor.trim().is_empty());
+    let editor = match editor {
+        Some(editor) => editor,
+        None => {
+            match line {
+                Some(line) => println!("{}:{}", display_path(path), line),
+                None => println!("{}", display_path(path)),
+            }
+            return ExitCode::SUCCESS;
+        }
+    };
+
+    // The editor may come with arguments of its own, like `code --wait`.
+    let mut words = editor.split_whitespace();
+    let mut command = std::process::Command::new(words.next().unwrap_or_default());
+    command.args(words);
+    if let Some(line) = line {
+        command.arg(format!("+{}", line));
+    }
+    match command.arg(path).status() {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(EXIT_ABORTED),
+        Err(e) => {
+            eprintln!("{}", trf("editor_failed", &[&editor, &e]));
+            ExitCode::from(EXIT_INVALID_ARGUMENTS)
+        }
+    }
+}
+
+// Parses the command line with the settings from the config file in front
+// of it. Usage errors exit with EXIT_INVALID_ARGUMENTS instead of clap's 2,
+// which stands for scan errors here.
+fn parse_args() -> Result<ArgMatches, ExitCode> {
+    let command_line = command_line().map_err(|e| {
+        eprintln!("{}", e);
+        ExitCode::from(EXIT_INVALID_ARGUMENTS)
+    })?;
+
+    cli()
+        .args_override_self(true)
+        .try_get_matches_from(command_line)
+        .map_err(|e| {
+            let _ = e.print();
+            if e.use_stderr() {
+                ExitCode::from(EXIT_INVALID_ARGUMENTS)
+            } else {
+                ExitCode::SUCCESS
+            }
+        })
+}
+
+// The program name and the settings from the config file, followed by the
+// arguments given on the command line.
+fn command_line() -> Result<Vec<OsString>, String> {
+    let mut argv: Vec<OsString> = std::env::args_os().collect();
+    let program = if argv.is_empty() {
+        OsString::from(env!("CARGO_PKG_NAME"))
+    } else {
+        argv.remove(0)
+    };
+    if let Some(path) = config_file_arg(&argv) {
+        set_config_file(path);
+    }
+    let settings = config_args(&cli(), profile_arg(&argv).as_deref())?;
+    Ok(std::iter::once(program)
+        .chain(settings)
+        .chain(argv)
+        .collect())
+}
+
+// The settings the TUI applies again when the config file changes.
+pub struct Settings {
+    pub palette: String,
+    pub keymap: Keymap,
+    pub columns: Vec<String>,
+}
+
+// Reads the config file again, with the command line still on top of it.
+pub fn reload_settings() -> Result<Settings, String> {
+    let args = cli()
+        .args_override_self(true)
+        .try_get_matches_from(command_line()?)
+        .map_err(|e| e.to_string().trim_end().to_string())?;
+    let keymap = keymap(&args).map_err(|e| trf("invalid_binding", &[&e]))?;
+    let (columns, _) = columns(&args);
+    Ok(Settings {
+        palette: args.get_one::<String>("palette").unwrap().clone(),
+        keymap,
+        columns,
+    })
+}
+
+// The columns to measure, and whether sizes are shown from the start.
+fn columns(args: &ArgMatches) -> (Vec<String>, bool) {
+    let mut columns: Vec<String> = args
+        .get_many::<String>("column")
+        .map(|columns| columns.cloned().collect())
+        .unwrap_or_default();
+
+    if args.get_flag("todo") && !columns.iter().any(|c| c == "todo") {
+        columns.push("todo".to_string());
+    }
+
+    // Sorting by a column needs its values even if it isn't displayed.
+    if let SortKey::Column(column) = SortKey::from_name(args.get_one::<String>("sort").unwrap()) {
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+
+    // Budgets are checked against the sizes of directories.
+    if args.contains_id("budget") && !columns.iter().any(|c| c == "disk" || c == "size") {
+        columns.push("disk".to_string());
+    }
+
+    // Binary files are found by a column that isn't shown, set up always as
+    // zb toggles hiding them.
+    columns.push("binary".to_string());
+
+    // Sizes are drawn at the right edge and can be toggled with Ctrl+K, so
+    // they are always set up even when they are hidden.
+    let show_size = args.get_flag("size") || columns.iter().any(|c| c == "size");
+    if !columns.iter().any(|c| c == "size") {
+        columns.push("size".to_string());
+    }
+    (columns, show_size)
+}
+
+fn keymap(args: &ArgMatches) -> Result<Keymap, String> {
+    let bindings: Vec<String> = args
+        .get_many::<String>("bind")
+        .map(|bindings| bindings.cloned().collect())
+        .unwrap_or_default();
+    Keymap::new(&bindings)
 }
 
 #[tokio::main]
-async fn main() {
-    let args = cli().get_matches();
+async fn main() -> ExitCode {
+    let mut args = match parse_args() {
+        Ok(args) => args,
+        Err(code) => return code,
+    };
 
-    let depth: Option<&String> = args.get_one("depth");
-    let dirname: Option<&String> = args.get_one("dirname");
+    // The first interactive start asks for the basic settings, which then
+    // apply to this start as well.
+    let interactive = !args.get_flag("batch")
+        && !args.get_flag("print")
+        && !args.get_flag("lint-names")
+        && !args.get_flag("lint-headers")
+        && !args.get_flag("update-snapshot")
+        && !args.contains_id("output")
+        && is_capable_terminal();
+    if interactive && config_path().is_some_and(|path| !path.exists()) {
+        match run_wizard(!args.get_flag("no-mouse")) {
+            Ok(message) => eprintln!("{}", message),
+            Err(e) => eprintln!("{}", trf("setup_failed", &[&e])),
+        }
+        args = match parse_args() {
+            Ok(args) => args,
+            Err(code) => return code,
+        };
+    }
+
+    let dirname: Option<&String> = args
+        .get_one("dirname")
+        .or_else(|| args.get_one("directory"));
 
     let dirname = match dirname {
         Some(d) => d,
         None => ".",
     };
 
+    if let Some(path) = args.get_one::<String>("debug-log") {
+        if let Err(e) = debug_log::init(Path::new(path)) {
+            eprintln!("{}: {}", path, e);
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    }
+
+    // A root given as a symlink is scanned at its target, with every path
+    // below it resolved the same way. The root is shown with its full path,
+    // or as the link and where it points, unless it is given a label.
+    let given = dirname;
     let dirname = match PathBuf::from(dirname).canonicalize() {
         Ok(path) => path,
         Err(e) => {
-            println!("Error: {}", e);
-            return;
+            eprintln!("{}: {}", dirname, e);
+            return ExitCode::from(EXIT_SCAN_ERROR);
         }
     };
+    let root_label = root_label(
+        given,
+        &dirname,
+        args.get_one::<String>("root-label").map(|s| s.as_str()),
+    );
+
+    // A virtual filesystem is still scanned when it is what was asked for.
+    if !args.get_flag("include-virtual") {
+        let mounts = virtual_mounts()
+            .into_iter()
+            .filter(|mount| *mount != dirname)
+            .collect();
+        let _ = SKIPPED_MOUNTS.set(mounts);
+    }
+
+    match Regex::new(args.get_one::<String>("license-header").unwrap()) {
+        Ok(header) => set_header(header),
+        Err(e) => {
+            eprintln!("{}", trf("invalid_pattern", &[&e]));
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    }
+
+    if let Some(file) = args.get_one::<String>("snapshot").map(Path::new) {
+        if args.get_flag("update-snapshot") {
+            let snapshot = Snapshot::take(&dirname);
+            return match snapshot.save(file) {
+                Ok(()) => {
+                    eprintln!("{}", trf("snapshot_saved", &[&snapshot.count()]));
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("{}", trf("snapshot_failed", &[&display_path(file), &e]));
+                    ExitCode::from(EXIT_SCAN_ERROR)
+                }
+            };
+        }
+        match Snapshot::load(file, &dirname) {
+            Ok(snapshot) => set_snapshot(snapshot),
+            Err(e) => {
+                eprintln!("{}", trf("snapshot_failed", &[&display_path(file), &e]));
+                return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+            }
+        }
+    }
+
+    // Everything is on a network mount when the storage says so.
+    let read_timeout = *args.get_one::<u64>("read-timeout").unwrap();
+    if read_timeout > 0 {
+        let mut mounts = network_mounts();
+        if args.get_one::<String>("storage").unwrap() == "network" {
+            mounts.push(dirname.clone());
+        }
+        let _ = NETWORK_MOUNTS.set(mounts);
+        let _ = READ_TIMEOUT.set(Duration::from_secs(read_timeout));
+    }
 
     let mut root = TreeNode {
         color: 33,
-        val: dirname.to_str().unwrap().to_string(),
+        val: node_name(&dirname),
         children: Vec::new(),
-        node_type: NodeType::Dir,
+        node_type: if dirname.is_dir() {
+            NodeType::Dir
+        } else {
+            NodeType::File
+        },
+    };
+
+    let color = if args.get_flag("depth-colors") {
+        ColorOptions::Depth
+    } else {
+        ColorOptions::NoColor
+    };
+
+    let selection_style = match args
+        .get_one::<String>("selection-style")
+        .map(|s| s.as_str())
+    {
+        Some("bar") => SelectionStyle::Bar,
+        _ => SelectionStyle::Name,
+    };
+
+    let zero_match_alert = match args
+        .get_one::<String>("zero-match-alert")
+        .map(|s| s.as_str())
+    {
+        Some("bell") => ZeroMatchAlert::Bell,
+        Some("flash") => ZeroMatchAlert::Flash,
+        _ => ZeroMatchAlert::None,
+    };
+
+    let todo = args.get_flag("todo");
+    let sort = SortKey::from_name(args.get_one::<String>("sort").unwrap());
+
+    let mut budgets = Vec::new();
+    for rule in args.get_many::<String>("budget").into_iter().flatten() {
+        match Budget::parse(rule) {
+            Ok(budget) => budgets.push(budget),
+            Err(e) => {
+                eprintln!("{}", trf("invalid_budget", &[&e]));
+                return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+            }
+        }
+    }
+
+    let (columns, show_size) = columns(&args);
+    let case_insensitive = match args.get_one::<String>("case").unwrap().as_str() {
+        "sensitive" => false,
+        "insensitive" => true,
+        _ => is_case_insensitive(&dirname),
+    };
+    let mut ignore = Vec::new();
+    for glob in args.get_many::<String>("ignore").into_iter().flatten() {
+        match compile_pattern(glob, MatchMode::Glob, case_insensitive) {
+            Ok(glob) => ignore.push(glob),
+            Err(e) => {
+                eprintln!("{}", trf("invalid_pattern", &[&e.display()]));
+                return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+            }
+        }
+    }
+    let keymap = match keymap(&args) {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            eprintln!("{}", trf("invalid_binding", &[&e]));
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    let time_format = match TimeFormat::parse(args.get_one::<String>("time-format").unwrap()) {
+        Ok(time_format) => time_format,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    let tags = match args
+        .get_one::<String>("tags")
+        .map(|path| Tags::load(Path::new(path)))
+    {
+        Some(Ok(tags)) => Some(tags),
+        Some(Err(e)) => {
+            eprintln!("{}", trf("tags_failed", &[&e]));
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+        None => None,
+    };
+
+    let scaffold = match args
+        .get_one::<String>("scaffold")
+        .map(|path| Scaffold::load(Path::new(path)))
+    {
+        Some(Ok(scaffold)) => Some(scaffold),
+        Some(Err(e)) => {
+            eprintln!("{}", trf("scaffold_failed", &[&e]));
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+        None => None,
+    };
+
+    let storage = Storage::from_name(args.get_one::<String>("storage").unwrap(), &dirname);
+    let mut tuning = storage.tuning();
+    if let Some(workers) = args.get_one::<usize>("workers") {
+        tuning.workers = *workers;
+    }
+
+    let backend = match backend(args.get_one::<String>("backend").unwrap()) {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
+    };
+
+    let options = DisplayOptions {
+        color,
+        root_label,
+        ls_colors: LsColors::from_env(),
+        icons: args.get_flag("icons"),
+        zebra: args.get_flag("zebra"),
+        selection_style,
+        status_format: args.get_one::<String>("status-format").unwrap().clone(),
+        zero_match_alert,
+        regex_debug: args.get_flag("regex-debug"),
+        git: args.get_flag("git"),
+        format: args.get_one::<String>("format").cloned(),
+        emit_events: args.get_flag("emit-events"),
+        script: args.get_one::<String>("script").map(PathBuf::from),
+        columns,
+        sort,
+        fold_level: args.get_one::<usize>("fold-level").copied(),
+        budgets,
+        show_size,
+        max_depth: args.get_one::<usize>("max-depth").copied(),
+        keymap,
+        vim: args.get_flag("vim"),
+        screen_reader: args.get_flag("screen-reader"),
+        palette: args.get_one::<String>("palette").unwrap().clone(),
+        time_format,
+        show_hidden: args.get_flag("hidden"),
+        hide_binaries: args.get_flag("hide-binaries"),
+        ignore,
+        only_case_conflicts: args.get_flag("case-conflicts"),
+        compact: args.get_flag("compact"),
+        dirs_first: args.get_flag("dirs-first"),
+        reverse: args.get_flag("reverse"),
+        watch: args.get_flag("watch"),
+        show_macos_metadata: args.get_flag("show-macos-metadata"),
+        match_mode: if todo {
+            MatchMode::Regex
+        } else {
+            MatchMode::from_name(args.get_one::<String>("match").unwrap())
+        },
+        content: todo || args.get_flag("content"),
+        top: *args.get_one::<usize>("top").unwrap(),
+        tags,
+        case_insensitive,
+        tuning,
+        backend,
+        page_size: args.get_one::<usize>("page-size").copied(),
+        mouse: !args.get_flag("no-mouse"),
+        screenshot: args.get_one::<String>("screenshot").map(PathBuf::from),
+        key_debug: args.get_flag("key-debug"),
+        tick: Duration::from_millis(*args.get_one::<u64>("tick").unwrap()),
+        from_stdin: args.get_flag("from-stdin"),
+        scaffold,
+    };
+
+    let pattern = match args.get_one::<String>("pattern") {
+        Some(pattern) => pattern.clone(),
+        None if todo => MARKER_PATTERN.to_string(),
+        None => String::new(),
+    };
+
+    info!(
+        root = %display_path(&dirname),
+        version = env!("CARGO_PKG_VERSION"),
+        term = std::env::var("TERM").unwrap_or_default(),
+        storage = ?storage,
+        workers = options.tuning.workers,
+        "starting"
+    );
+
+    // Another instance showing the same directory is reported. When asking
+    // is possible the user decides whether to scan again, otherwise this
+    // one goes ahead.
+    let _lock = if args.get_flag("lock") {
+        match acquire(&dirname) {
+            Ok(Acquired::Locked(lock)) => Some(lock),
+            Ok(Acquired::Held(pid)) => {
+                let pid = pid.map_or("?".to_string(), |pid| pid.to_string());
+                eprintln!("{}", trf("lock_held", &[&pid, &display_path(&dirname)]));
+                if !options.from_stdin && std::io::stdin().is_terminal() {
+                    eprint!("{} ", tr("lock_prompt"));
+                    let mut answer = String::new();
+                    let _ = std::io::stdin().read_line(&mut answer);
+                    if !matches!(answer.trim(), "y" | "Y") {
+                        return ExitCode::from(EXIT_ABORTED);
+                    }
+                }
+                None
+            }
+            Err(e) => {
+                eprintln!("{}", trf("lock_failed", &[&e]));
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if options.from_stdin {
+        read_paths(&mut root, &dirname, std::io::stdin().lock());
+    }
+    if let Some(scaffold) = &options.scaffold {
+        let dry_run = args.get_flag("dry-run");
+        return run_scaffold(
+            &mut root,
+            scaffold,
+            &dirname,
+            &pattern,
+            &options,
+            interactive && !dry_run,
+            dry_run,
+        );
+    }
+
+    // Paths with line breaks in them can only be told apart with NUL.
+    let separator = if args.get_flag("print0") { '\0' } else { '\n' };
+    let list_file = args.get_one::<String>("list-file");
+
+    // Terminals that can't show the TUI get the batch output instead of
+    // escapes they don't understand.
+    if interactive {
+        match render(&mut root, dirname.clone(), pattern.clone(), &options) {
+            Ok(Outcome::Selected(path)) => {
+                if let Err(e) = remember(&path) {
+                    warn!(error = %e, "remembering the selection failed");
+                }
+                if let Some(file) = list_file {
+                    return write_list(&[relative_to(&dirname, &path)], separator, file);
+                }
+                print!("{}{}", display_path(&path), separator);
+                return ExitCode::SUCCESS;
+            }
+            Ok(Outcome::Marked(paths)) => {
+                for path in &paths {
+                    if let Err(e) = remember(path) {
+                        warn!(error = %e, "remembering the selection failed");
+                    }
+                }
+                if let Some(file) = list_file {
+                    let list: Vec<PathBuf> = paths
+                        .iter()
+                        .map(|path| relative_to(&dirname, path))
+                        .collect();
+                    return write_list(&list, separator, file);
+                }
+                for path in &paths {
+                    print!("{}{}", display_path(path), separator);
+                }
+                return ExitCode::SUCCESS;
+            }
+            Ok(Outcome::Symbol(path, line)) => {
+                if let Err(e) = remember(&path) {
+                    warn!(error = %e, "remembering the selection failed");
+                }
+                return open_editor(&path, line);
+            }
+            Ok(Outcome::Aborted) => return ExitCode::from(EXIT_ABORTED),
+            Err(e) => eprintln!("{}", trf("no_tui", &[&e])),
+        }
+    }
+
+    let filter = match compile_pattern(&pattern, options.match_mode, options.case_insensitive) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("{}", trf("invalid_pattern", &[&e.display()]));
+            return ExitCode::from(EXIT_INVALID_ARGUMENTS);
+        }
     };
 
-    render(&mut root, dirname.clone());
+    let started = Instant::now();
+    if !options.from_stdin {
+        read_dir_complete(
+            &mut root,
+            dirname.clone(),
+            options.max_depth,
+            options.backend,
+        );
+    }
+    debug!(elapsed = ?started.elapsed(), "scanned");
+    if !options.show_hidden
+        || !options.show_macos_metadata
+        || options.hide_binaries
+        || !options.ignore.is_empty()
+    {
+        let binary = provider("binary").filter(|_| options.hide_binaries);
+        let is_binary = |path: &Path, node: &TreeNode| {
+            binary
+                .as_ref()
+                .is_some_and(|binary| binary.annotate(path, node.node_type).is_some())
+        };
+        root = prune_tree(&root, &dirname, &|path, node| {
+            (options.show_hidden || !is_dotfile(&node.val))
+                && (options.show_macos_metadata || !is_macos_metadata(&node.val))
+                && !is_binary(path, node)
+                && !options.ignore.iter().any(|glob| glob.is_match(&node.val))
+        });
+    }
+    // Content matches are found here, so the output keeps everything left.
+    let filter = if options.content && !pattern.is_empty() {
+        root = content_tree(&root, &dirname, &|path| {
+            first_match(path, &filter).is_some()
+        });
+        Regex::new("").unwrap()
+    } else {
+        filter
+    };
+    if options.only_case_conflicts {
+        let mut conflicts = HashSet::new();
+        case_conflicts(&root, &dirname, &mut conflicts);
+        root = only_case_conflicts(&root, &dirname, &conflicts);
+    }
+
+    // The TUI measures columns in the background; here the sort column is
+    // measured for each entry as it is sorted.
+    let sort_provider = match &options.sort {
+        SortKey::Column(name) => provider(name),
+        _ => None,
+    };
+    sort_tree_by_setting(
+        &mut root,
+        &dirname,
+        &options.sort,
+        &|_, path, node_type| {
+            let value = sort_provider.as_ref()?.annotate(path, node_type)?;
+            leading_number(&value)
+        },
+        &mut HashMap::new(),
+    );
+    order_tree(&mut root, &dirname, options.reverse, options.dirs_first);
+
+    // Globs with a slash match paths from the root, as in the TUI.
+    let by_path = !options.content && matches_paths(&pattern, options.match_mode);
+    let matched = |path: &Path, name: &str| {
+        if by_path {
+            filter.is_match(&relative_path(&dirname, path))
+        } else {
+            filter.is_match(name)
+        }
+    };
+    let filtered = if by_path {
+        filter_tree_by_path(&root, &dirname, &|path| matched(path, ""))
+    } else {
+        filter_tree(&root, &filter)
+    };
+
+    if options.emit_events {
+        let entries = get_tree_count(&root, NodeType::File) + get_tree_count(&root, NodeType::Dir);
+        emit_scan(entries, true);
+        let matches = if by_path {
+            count_path_matches(&root, &dirname, &|path| matched(path, ""))
+        } else {
+            count_matches(&root, &filter)
+        };
+        emit_matches(&pattern, matches);
+    }
+
+    let output = args.get_one::<String>("output").map(|s| s.as_str());
+    if args.get_flag("lint-names") {
+        let report = portability_report(&filtered, &dirname);
+        for finding in &report.entries {
+            println!("{}", finding.display(&dirname));
+        }
+    } else if args.get_flag("lint-headers") {
+        for path in missing_headers(&filtered, &dirname, license::header()) {
+            println!(
+                "{}",
+                display_path(path.strip_prefix(&dirname).unwrap_or(&path))
+            );
+        }
+    } else if output == Some("mkdir-script") {
+        print!("{}", mkdir_script(&filtered, args.get_flag("gitkeep")));
+    } else if output == Some("files-from") {
+        let mut list = Vec::new();
+        include_list(&filtered, Path::new(""), &mut list);
+        match list_file {
+            Some(file) => {
+                if let Err(e) = std::fs::write(file, list_text(&list, separator)) {
+                    eprintln!("{}", trf("list_failed", &[&file, &e]));
+                    return ExitCode::from(EXIT_SCAN_ERROR);
+                }
+            }
+            None => print!("{}", list_text(&list, separator)),
+        }
+    } else if output == Some("json") {
+        println!("{}", json_tree(&filtered, &dirname, &matched));
+    } else if args.get_flag("print") || output == Some("tree") {
+        let color = match options.color {
+            _ if !std::io::stdout().is_terminal() || !ansi_supported() => ColorOptions::NoColor,
+            ColorOptions::Depth => ColorOptions::Depth,
+            _ => ColorOptions::Default,
+        };
+        let mut tree = filtered;
+        if options.compact {
+            tree = compact_tree(&tree);
+        }
+        tree.val = options.root_label.clone();
+        print!(
+            "{}",
+            print_tree(
+                &tree,
+                &dirname,
+                &[],
+                &color,
+                options.ls_colors.as_ref(),
+                options.icons,
+            )
+        );
+    } else {
+        let template = options.format.as_deref().unwrap_or(DEFAULT_BATCH_FORMAT);
+        print_batch(
+            &root,
+            &dirname,
+            0,
+            &filter,
+            &matched,
+            template,
+            &options.time_format,
+        );
+    }
+
+    match SCAN_ERRORS.load(Ordering::Relaxed) {
+        0 => ExitCode::SUCCESS,
+        errors => {
+            eprintln!("{}", trf("scan_errors", &[&errors]));
+            ExitCode::from(EXIT_SCAN_ERROR)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A directory of its own below the temporary directory, canonical.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tree-rs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("root")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    fn label_of(given: &str) -> String {
+        let dirname = Path::new(given).canonicalize().unwrap();
+        root_label(given, &dirname, None)
+    }
+
+    #[test]
+    fn dot_is_shown_as_the_full_path() {
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(label_of("."), display_path(&cwd));
+    }
+
+    #[test]
+    fn dot_dot_is_shown_as_the_full_path() {
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(label_of(".."), display_path(cwd.parent().unwrap()));
+    }
+
+    #[test]
+    fn trailing_slash_is_dropped() {
+        let dir = scratch("slash");
+        let given = format!("{}/", display_path(&dir.join("root")));
+        assert_eq!(label_of(&given), display_path(&dir.join("root")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filesystem_root_is_shown_as_a_slash() {
+        assert_eq!(label_of("/"), "/");
+        assert_eq!(node_name(Path::new("/")), "/");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_root_shows_where_it_points() {
+        let dir = scratch("link");
+        let link = dir.join("link");
+        std::os::unix::fs::symlink(dir.join("root"), &link).unwrap();
+        let target = display_path(&dir.join("root"));
+
+        let given = display_path(&link);
+        assert_eq!(label_of(&given), format!("{} → {}", given, target));
+        let given = format!("{}/", given);
+        assert_eq!(label_of(&given), format!("{} → {}", given, target));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn label_replaces_the_path() {
+        let dirname = Path::new(".").canonicalize().unwrap();
+        assert_eq!(root_label(".", &dirname, Some("project")), "project");
+    }
 }
@@ -0,0 +1,116 @@
+// Palettes pick the colors of the interface. Whatever the palette, every
+// state is also marked by a modifier (errors are bold, the offending part of
+// a pattern is underlined, the selection is reversed), so none of them
+// depends on telling red from green.
+
+use crate::jump_list::JumpList;
+use tui::style::Color;
+
+#[derive(Clone, Copy)]
+pub struct Palette {
+    // Pattern errors and the zero match warning.
+    pub error: Color,
+    // Background of the status bar when it flashes.
+    pub flash: Color,
+    // The matched part of a name in the regex debugging panel.
+    pub highlight: Color,
+    pub jump_label: Color,
+    // Extra columns and fold markers.
+    pub dim: Color,
+    pub zebra: Color,
+    // Git status markers. Ignored entries are marked in the dim color.
+    pub git_staged: Color,
+    pub git_modified: Color,
+    pub git_untracked: Color,
+    // Colors of successive tree levels with --depth-colors.
+    pub depth: [u8; 6],
+}
+
+impl Palette {
+    // Deeper levels wrap around to the start of the ramp.
+    pub fn depth_color(&self, depth: usize) -> u8 {
+        self.depth[depth % self.depth.len()]
+    }
+}
+
+pub const DEFAULT_PALETTE: Palette = Palette {
+    error: Color::Red,
+    flash: Color::Red,
+    highlight: Color::Yellow,
+    jump_label: Color::Yellow,
+    dim: Color::DarkGray,
+    zebra: Color::Indexed(236),
+    git_staged: Color::Green,
+    git_modified: Color::Yellow,
+    git_untracked: Color::Red,
+    // A blue to green ramp.
+    depth: [39, 38, 37, 36, 35, 34],
+};
+
+// Bright colors on black and light gray instead of dark gray.
+const HIGH_CONTRAST_PALETTE: Palette = Palette {
+    error: Color::Indexed(196),
+    flash: Color::Indexed(196),
+    highlight: Color::Indexed(226),
+    jump_label: Color::Indexed(226),
+    dim: Color::Indexed(250),
+    zebra: Color::Indexed(238),
+    git_staged: Color::Indexed(46),
+    git_modified: Color::Indexed(226),
+    git_untracked: Color::Indexed(196),
+    depth: [231, 226, 51, 231, 226, 51],
+};
+
+// Blue and orange from the Okabe-Ito palette, which stay apart for both
+// deuteranopia and protanopia.
+const COLORBLIND_PALETTE: Palette = Palette {
+    error: Color::Indexed(208),
+    flash: Color::Indexed(208),
+    highlight: Color::Indexed(75),
+    jump_label: Color::Indexed(75),
+    dim: Color::Indexed(245),
+    zebra: Color::Indexed(236),
+    git_staged: Color::Indexed(75),
+    git_modified: Color::Indexed(208),
+    git_untracked: Color::Indexed(222),
+    // A blue to orange ramp.
+    depth: [33, 75, 117, 222, 215, 208],
+};
+
+pub const PALETTE_NAMES: [&str; 5] = [
+    "default",
+    "high-contrast",
+    "colorblind",
+    "deuteranopia",
+    "protanopia",
+];
+
+pub fn palette(name: &str) -> Option<Palette> {
+    match name {
+        "default" => Some(DEFAULT_PALETTE),
+        "high-contrast" => Some(HIGH_CONTRAST_PALETTE),
+        "colorblind" | "deuteranopia" | "protanopia" => Some(COLORBLIND_PALETTE),
+        _ => None,
+    }
+}
+
+// The theme picker. Each palette is applied as soon as it is selected, so
+// the tree behind the picker previews it, and cancelling goes back to the
+// one in use when the picker was opened.
+pub struct ThemePicker {
+    pub list: JumpList<&'static str>,
+    pub original: String,
+}
+
+impl ThemePicker {
+    pub fn new(current: &str) -> ThemePicker {
+        let selected = PALETTE_NAMES
+            .iter()
+            .position(|name| *name == current)
+            .unwrap_or(0);
+        ThemePicker {
+            list: JumpList::wrapping(PALETTE_NAMES.to_vec(), selected),
+            original: current.to_string(),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{i18n::trf, palette::Palette};
+use regex::{Regex, RegexBuilder};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+pub struct Search {
+    pub filter: Regex,
+    pub error: Option<PatternError>,
+    pub matches: usize,
+}
+
+pub struct PatternError {
+    pub message: String,
+    // Character offset of the offending part of the pattern, if known.
+    pub position: Option<usize>,
+}
+
+impl PatternError {
+    pub fn display(&self) -> String {
+        match self.position {
+            Some(position) => trf("pattern_error_at", &[&(position + 1), &self.message]),
+            None => trf("pattern_error", &[&self.message]),
+        }
+    }
+}
+
+// Patterns are regexes, or with fuzzy matching the characters of a name in
+// order with anything between them, like fzf: `mrs` matches main.rs. Fuzzy
+// patterns ignore case unless they contain an uppercase letter. Globs match
+// whole names like the shell does, e.g. `*.rs`, and once they contain a
+// slash whole paths from the root, e.g. `src/**/test_*`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    Regex,
+    Fuzzy,
+    Glob,
+}
+
+pub const MATCH_MODE_NAMES: [&str; 3] = ["regex", "fuzzy", "glob"];
+
+impl MatchMode {
+    pub fn from_name(name: &str) -> MatchMode {
+        match name {
+            "fuzzy" => MatchMode::Fuzzy,
+            "glob" => MatchMode::Glob,
+            _ => MatchMode::Regex,
+        }
+    }
+}
+
+// Whether the pattern is matched against paths from the root rather than
+// names.
+pub fn matches_paths(pattern: &str, mode: MatchMode) -> bool {
+    mode == MatchMode::Glob && pattern.contains('/')
+}
+
+// Translates a glob into a regex matching the same names or paths: * and ?
+// don't cross slashes, ** does, [...] and [!...] are character classes and
+// {a,b} matches either.
+pub fn glob_to_regex(glob: &str) -> String {
+    // Like an empty regex, an empty glob keeps everything.
+    if glob.is_empty() {
+        return String::new();
+    }
+
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    let mut braces = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                match class.strip_prefix('!') {
+                    Some(negated) => regex.push_str(&format!("[^{}]", negated)),
+                    None => regex.push_str(&format!("[{}]", class)),
+                }
+            }
+            '{' => {
+                braces += 1;
+                regex.push_str("(?:");
+            }
+            ',' if braces > 0 => regex.push('|'),
+            '}' if braces > 0 => {
+                braces -= 1;
+                regex.push(')');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+pub fn compile_pattern(
+    pattern: &str,
+    mode: MatchMode,
+    case_insensitive: bool,
+) -> Result<Regex, PatternError> {
+    if mode == MatchMode::Glob {
+        return RegexBuilder::new(&glob_to_regex(pattern))
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| PatternError {
+                message: e.to_string(),
+                position: None,
+            });
+    }
+
+    if mode == MatchMode::Fuzzy {
+        let subsequence: Vec<String> = pattern
+            .chars()
+            .map(|c| regex::escape(&c.to_string()))
+            .collect();
+        return RegexBuilder::new(&subsequence.join(".*?"))
+            .case_insensitive(case_insensitive || !has_uppercase(pattern))
+            .build()
+            .map_err(|e| PatternError {
+                message: e.to_string(),
+                position: None,
+            });
+    }
+
+    // The regex crate only reports errors as preformatted text, so the
+    // pattern is parsed separately first to get at the error span.
+    let parsed = regex_syntax::ParserBuilder::new()
+        .case_insensitive(case_insensitive)
+        .build()
+        .parse(pattern);
+    if let Err(e) = parsed {
+        let (message, offset) = match &e {
+            regex_syntax::Error::Parse(e) => (e.kind().to_string(), e.span().start.offset),
+            regex_syntax::Error::Translate(e) => (e.kind().to_string(), e.span().start.offset),
+            _ => (e.to_string(), 0),
+        };
+
+        return Err(PatternError {
+            message,
+            position: Some(pattern[..offset].chars().count()),
+        });
+    }
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| PatternError {
+            message: e.to_string(),
+            position: None,
+        })
+}
+
+// Describes how the pattern matched a name, for the regex debugging panel:
+// the name with the overall match highlighted, followed by one line per
+// capture group.
+pub fn describe_match(filter: &Regex, name: &str, palette: &Palette) -> Vec<Spans<'static>> {
+    let captures = match filter.captures(name) {
+        Some(captures) => captures,
+        None => return vec![Spans::from(trf("no_match", &[&name]))],
+    };
+
+    let whole = captures.get(0).unwrap();
+    let highlight = Style::default()
+        .fg(palette.highlight)
+        .add_modifier(Modifier::UNDERLINED);
+
+    let mut lines = vec![Spans::from(vec![
+        Span::raw(name[..whole.start()].to_string()),
+        Span::styled(whole.as_str().to_string(), highlight),
+        Span::raw(name[whole.end()..].to_string()),
+        Span::raw(format!("  [{}..{}]", whole.start(), whole.end())),
+    ])];
+
+    for (i, group_name) in filter.capture_names().enumerate().skip(1) {
+        let label = match group_name {
+            Some(group_name) => format!("${} ({})", i, group_name),
+            None => format!("${}", i),
+        };
+
+        lines.push(match captures.get(i) {
+            Some(group) => Spans::from(vec![
+                Span::raw(format!("{}: ", label)),
+                Span::styled(group.as_str().to_string(), highlight),
+                Span::raw(format!("  [{}..{}]", group.start(), group.end())),
+            ]),
+            None => Spans::from(trf("not_participating", &[&label])),
+        });
+    }
+
+    lines
+}
+
+fn has_uppercase(pattern: &str) -> bool {
+    pattern.chars().any(char::is_uppercase)
+}
+
+// The character positions of a fuzzy pattern in a name, each taken as early
+// as possible, or None if it doesn't match.
+pub fn fuzzy_positions(pattern: &str, name: &str, case_insensitive: bool) -> Option<Vec<usize>> {
+    let fold = case_insensitive || !has_uppercase(pattern);
+    let same = |a: char, b: char| a == b || fold && a.to_lowercase().eq(b.to_lowercase());
+
+    let mut positions = Vec::new();
+    let mut name = name.chars().enumerate();
+    for c in pattern.chars() {
+        let (position, _) = name.find(|(_, n)| same(c, *n))?;
+        positions.push(position);
+    }
+    Some(positions)
+}
+
+// How well a fuzzy pattern matches a name, higher being better. Like fzf,
+// characters that follow each other or start a word count for more, and
+// gaps and long names count against a match.
+pub fn fuzzy_score(pattern: &str, name: &str, case_insensitive: bool) -> Option<u64> {
+    let positions = fuzzy_positions(pattern, name, case_insensitive)?;
+    let chars: Vec<char> = name.chars().collect();
+
+    let mut score: u64 = 0;
+    for (i, &position) in positions.iter().enumerate() {
+        score += 16;
+        if i > 0 && positions[i - 1] + 1 == position {
+            score += 24;
+        }
+        let starts_word = match position.checked_sub(1).map(|previous| chars[previous]) {
+            None => true,
+            Some(previous) => {
+                !previous.is_alphanumeric()
+                    || previous.is_lowercase() && chars[position].is_uppercase()
+            }
+        };
+        if starts_word {
+            score += 20;
+        }
+    }
+
+    let gaps = match (positions.first(), positions.last()) {
+        (Some(first), Some(last)) => last - first + 1 - positions.len(),
+        _ => 0,
+    };
+    Some(score.saturating_sub(2 * gaps as u64 + chars.len() as u64 / 4))
+}
@@ -0,0 +1,196 @@
+// Plugins are executables in `~/.config/tree-rs/plugins/`. Each one is bound
+// to Alt plus the first character of its file name. When triggered, the
+// plugin receives the tree context as a JSON object on stdin:
+//
+//     {"root": "/path", "pattern": "regex", "selected": ["/path/file"]}
+//
+// and replies on stdout with one command per line:
+//
+//     annotate <path>\t<text>   show text in an extra column after the name
+//     color <path>\t<color>     color the entry (a name or 256 color index)
+//     message <text>            show the text in the status bar
+//     filter <pattern>          replace the search pattern
+//
+// Unknown commands are ignored. A plugin that hasn't finished after
+// PLUGIN_TIMEOUT is killed, so a hung one can't freeze the TUI.
+
+use crate::{
+    events::json_string,
+    i18n::trf,
+    util::{config_dir, display_path, parse_color},
+};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+use tui::style::Color;
+
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Plugin {
+    pub key: char,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Default)]
+pub struct Annotation {
+    pub text: Option<String>,
+    pub color: Option<Color>,
+}
+
+pub enum PluginAction {
+    Message(String),
+    Filter(String),
+}
+
+pub fn plugin_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join("plugins"))
+}
+
+pub fn load_plugins() -> Vec<Plugin> {
+    let entries = match plugin_dir().map(std::fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return Vec::new(),
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_executable(path))
+        .collect();
+    paths.sort();
+
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let key = match name.chars().next() {
+            Some(key) => key.to_ascii_lowercase(),
+            None => continue,
+        };
+
+        // The first plugin to claim a key keeps it.
+        if plugins.iter().any(|plugin| plugin.key == key) {
+            continue;
+        }
+
+        plugins.push(Plugin { key, name, path });
+    }
+
+    plugins
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    match std::fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+pub fn run_plugin(
+    plugin: &Plugin,
+    root: &Path,
+    pattern: &str,
+    selected: &[PathBuf],
+    annotations: &mut HashMap<PathBuf, Annotation>,
+) -> Vec<PluginAction> {
+    let selected: Vec<String> = selected
+        .iter()
+        .map(|path| json_string(&display_path(path)))
+        .collect();
+    let input = format!(
+        "{{\"root\":{},\"pattern\":{},\"selected\":[{}]}}\n",
+        json_string(&display_path(root)),
+        json_string(pattern),
+        selected.join(",")
+    );
+
+    let child = Command::new(&plugin.path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            return vec![PluginAction::Message(trf(
+                "plugin_failed",
+                &[&plugin.name, &e],
+            ))]
+        }
+    };
+
+    // The pipes are served by threads, so a plugin that stops reading or
+    // writing doesn't block this one. A plugin that exits without reading
+    // its input is not an error.
+    if let Some(mut stdin) = child.stdin.take() {
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    let (output_tx, output_rx) = mpsc::channel();
+    if let Some(mut stdout) = child.stdout.take() {
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = stdout.read_to_end(&mut output);
+            let _ = output_tx.send(output);
+        });
+    }
+
+    let deadline = Instant::now() + PLUGIN_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return vec![PluginAction::Message(trf(
+                    "plugin_timed_out",
+                    &[&plugin.name, &PLUGIN_TIMEOUT.as_secs()],
+                ))];
+            }
+            Err(e) => return vec![PluginAction::Message(format!("{}: {}", plugin.name, e))],
+        }
+    }
+    // Something the plugin started may still hold the output open.
+    let output = output_rx
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .unwrap_or_default();
+
+    let mut actions = Vec::new();
+    for line in String::from_utf8_lossy(&output).lines() {
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "annotate" | "color" => {
+                let (path, value) = match argument.split_once('\t') {
+                    Some(pair) => pair,
+                    None => continue,
+                };
+                let annotation = annotations.entry(PathBuf::from(path)).or_default();
+                if command == "annotate" {
+                    annotation.text = Some(value.to_string());
+                } else {
+                    annotation.color = parse_color(value);
+                }
+            }
+            "message" => actions.push(PluginAction::Message(argument.to_string())),
+            "filter" => actions.push(PluginAction::Filter(argument.to_string())),
+            _ => {}
+        }
+    }
+
+    actions
+}
@@ -0,0 +1,133 @@
+// Names that are fine here but can't be copied as they are to other systems.
+// Windows rejects the characters <>:"|?* and control characters, device
+// names such as CON or LPT1 with any extension, and names ending in a space
+// or a dot. Paths longer than 260 characters break many Windows programs.
+// macOS used to store names decomposed, so an é from there is an e followed
+// by a combining accent, which looks the same but doesn't match the composed
+// é typed elsewhere. Without the Unicode tables to normalize names, combining
+// accents are taken as the sign of a decomposed name.
+
+use crate::{
+    i18n::{tr, trf},
+    jump_list::JumpList,
+    util::display_path,
+    TreeNode,
+};
+use std::path::{Path, PathBuf};
+
+const RESERVED_CHARACTERS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+const MAX_NAME: usize = 255;
+const MAX_PATH: usize = 260;
+
+pub enum Problem {
+    ReservedCharacter(char),
+    ControlCharacter,
+    ReservedName,
+    TrailingSpace,
+    TrailingDot,
+    LongName(usize),
+    LongPath(usize),
+    Decomposed,
+}
+
+impl Problem {
+    pub fn describe(&self) -> String {
+        match self {
+            Problem::ReservedCharacter(c) => trf("reserved_character", &[c]),
+            Problem::ControlCharacter => tr("control_character"),
+            Problem::ReservedName => tr("reserved_name"),
+            Problem::TrailingSpace => tr("trailing_space"),
+            Problem::TrailingDot => tr("trailing_dot"),
+            Problem::LongName(length) => trf("long_name", &[length, &MAX_NAME]),
+            Problem::LongPath(length) => trf("long_path", &[length, &MAX_PATH]),
+            Problem::Decomposed => tr("decomposed"),
+        }
+    }
+}
+
+// The problems of a name, given the length of its path from the root.
+pub fn problems(name: &str, path_length: usize) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let mut reserved: Vec<char> = name
+        .chars()
+        .filter(|c| RESERVED_CHARACTERS.contains(c))
+        .collect();
+    reserved.dedup();
+    problems.extend(reserved.into_iter().map(Problem::ReservedCharacter));
+    if name.chars().any(|c| c.is_control()) {
+        problems.push(Problem::ControlCharacter);
+    }
+
+    let stem = name.split('.').next().unwrap_or("").to_uppercase();
+    let device = stem.strip_prefix("COM").or(stem.strip_prefix("LPT"));
+    if RESERVED_NAMES.contains(&stem.as_str())
+        || device.is_some_and(|n| n.len() == 1 && matches!(n.as_bytes()[0], b'1'..=b'9'))
+    {
+        problems.push(Problem::ReservedName);
+    }
+
+    if name.ends_with(' ') {
+        problems.push(Problem::TrailingSpace);
+    } else if name.ends_with('.') {
+        problems.push(Problem::TrailingDot);
+    }
+
+    // Windows counts UTF-16 code units.
+    let length = name.encode_utf16().count();
+    if length > MAX_NAME {
+        problems.push(Problem::LongName(length));
+    }
+    if path_length > MAX_PATH {
+        problems.push(Problem::LongPath(path_length));
+    }
+
+    if name.chars().any(|c| ('\u{300}'..='\u{36f}').contains(&c)) {
+        problems.push(Problem::Decomposed);
+    }
+
+    problems
+}
+
+pub struct Finding {
+    pub path: PathBuf,
+    pub problems: Vec<Problem>,
+}
+
+impl Finding {
+    pub fn display(&self, root: &Path) -> String {
+        let path = self.path.strip_prefix(root).unwrap_or(&self.path);
+        let problems: Vec<String> = self.problems.iter().map(Problem::describe).collect();
+        format!("{}: {}", display_path(path), problems.join(", "))
+    }
+}
+
+// The names below the root that aren't portable, as Ctrl+N and
+// --lint-names list them.
+pub fn portability_report(root: &TreeNode, path: &Path) -> JumpList<Finding> {
+    let mut findings = Vec::new();
+    find_problems(root, path, 0, &mut findings);
+    JumpList::new(findings)
+}
+
+// Paths are measured from the root, which is where a copy would start. Only
+// the entries that cross the limit are reported as too long, not everything
+// below them.
+fn find_problems(root: &TreeNode, path: &Path, length: usize, findings: &mut Vec<Finding>) {
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        let child_length = length + usize::from(length > 0) + child.val.encode_utf16().count();
+        let crossed = if length <= MAX_PATH { child_length } else { 0 };
+        let problems = problems(&child.val, crossed);
+        if !problems.is_empty() {
+            findings.push(Finding {
+                path: child_path.clone(),
+                problems,
+            });
+        }
+        find_problems(child, &child_path, child_length, findings);
+    }
+}
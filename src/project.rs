@@ -0,0 +1,75 @@
+use crate::{jump_list::JumpList, NodeType, TreeNode};
+
+pub const PROJECT_MARKERS: [&str; 4] = ["Cargo.toml", "package.json", "go.mod", ".git"];
+
+// Directories that are never searched for nested projects, since they are
+// either vendored dependencies or version control internals.
+const SKIPPED_DIRS: [&str; 2] = [".git", "node_modules"];
+
+pub struct Project {
+    pub path: Vec<String>,
+    pub markers: Vec<&'static str>,
+}
+
+impl Project {
+    pub fn display(&self) -> String {
+        let path = if self.path.is_empty() {
+            ".".to_string()
+        } else {
+            self.path.join("/")
+        };
+
+        if self.markers.is_empty() {
+            path
+        } else {
+            format!("{}  [{}]", path, self.markers.join(", "))
+        }
+    }
+}
+
+// The root and the projects below it.
+pub fn project_list(root: &TreeNode) -> JumpList<Project> {
+    let mut entries = vec![Project {
+        path: Vec::new(),
+        markers: project_markers(root),
+    }];
+    find_projects(root, &mut Vec::new(), &mut entries);
+    JumpList::new(entries)
+}
+
+pub fn project_markers(node: &TreeNode) -> Vec<&'static str> {
+    PROJECT_MARKERS
+        .iter()
+        .filter(|marker| node.children.iter().any(|child| child.val == **marker))
+        .copied()
+        .collect()
+}
+
+fn find_projects(root: &TreeNode, path: &mut Vec<String>, projects: &mut Vec<Project>) {
+    for child in &root.children {
+        if child.node_type != NodeType::Dir || SKIPPED_DIRS.contains(&child.val.as_str()) {
+            continue;
+        }
+
+        path.push(child.val.clone());
+
+        let markers = project_markers(child);
+        if !markers.is_empty() {
+            projects.push(Project {
+                path: path.clone(),
+                markers,
+            });
+        }
+
+        find_projects(child, path, projects);
+        path.pop();
+    }
+}
+
+pub fn subtree<'a>(root: &'a TreeNode, path: &[String]) -> Option<&'a TreeNode> {
+    let mut node = root;
+    for component in path {
+        node = node.children.iter().find(|child| &child.val == component)?;
+    }
+    Some(node)
+}
@@ -0,0 +1,44 @@
+// The quick view lists only the best few matches instead of the whole tree,
+// for when the pattern is just a way to get to one file. Fuzzy matches are
+// ranked by their score, then by frecency, and the rest by the length of
+// their paths, as the shortest path is usually the one that was meant. The
+// pattern can still be edited while the view is open, and the list follows
+// it.
+
+use crate::TreeNode;
+use std::path::{Path, PathBuf};
+
+// The `count` best entries of a tree that are `matched`. Entries with a
+// higher score come first and, among equals, the ones with shorter paths.
+pub fn best_matches<K: Ord + Copy>(
+    root: &TreeNode,
+    path: &Path,
+    count: usize,
+    matched: &dyn Fn(&Path, &TreeNode) -> bool,
+    score: &dyn Fn(&Path, &str) -> Option<K>,
+) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    find_matches(root, path, matched, score, &mut found);
+    found.sort_by_key(|(score, path)| (std::cmp::Reverse(*score), path.as_os_str().len()));
+    found
+        .into_iter()
+        .take(count)
+        .map(|(_, path)| path)
+        .collect()
+}
+
+fn find_matches<K>(
+    root: &TreeNode,
+    path: &Path,
+    matched: &dyn Fn(&Path, &TreeNode) -> bool,
+    score: &dyn Fn(&Path, &str) -> Option<K>,
+    found: &mut Vec<(Option<K>, PathBuf)>,
+) {
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        if matched(&child_path, child) {
+            found.push((score(&child_path, &child.val), child_path.clone()));
+        }
+        find_matches(child, &child_path, matched, score, found);
+    }
+}
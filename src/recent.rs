@@ -0,0 +1,148 @@
+// Entries picked with Enter are remembered in `~/.config/tree-rs/recent`,
+// one per line with the latest first, so that later sessions can jump back
+// to them. Ctrl+W lists the ones below the directory being shown.
+//
+// Each line also counts how often the entry was picked and says when it was
+// picked last, as `count<TAB>seconds since 1970<TAB>path`. Together they
+// give its frecency, like zoxide's for directories: the count weighted by
+// how recent the last pick was. Matches that are otherwise equal are
+// ordered by it, so entries picked often and lately come first.
+
+use crate::{
+    jump_list::JumpList,
+    util::{config_dir, display_path},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// How many entries are remembered.
+const MAX_RECENT: usize = 100;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+pub struct Visit {
+    pub path: PathBuf,
+    pub count: u64,
+    // When it was picked last, in seconds since 1970.
+    pub last: u64,
+}
+
+impl Visit {
+    // The count weighted by age, four times as much within the hour and
+    // twice as much within the day as within the week, and half as much
+    // after that.
+    pub fn frecency(&self, now: u64) -> u64 {
+        let age = now.saturating_sub(self.last);
+        let weight = match age {
+            _ if age < HOUR => 16,
+            _ if age < DAY => 8,
+            _ if age < WEEK => 4,
+            _ => 2,
+        };
+        self.count * weight
+    }
+}
+
+fn recent_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("recent"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+// The remembered entries, the latest first. A missing or unreadable file
+// has none. Lines with just a path, as written before entries were counted,
+// count once.
+pub fn visits() -> Vec<Visit> {
+    let text = recent_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(count), Some(last), Some(path)) => Visit {
+                    path: PathBuf::from(path),
+                    count: count.parse().unwrap_or(1),
+                    last: last.parse().unwrap_or(0),
+                },
+                _ => Visit {
+                    path: PathBuf::from(line),
+                    count: 1,
+                    last: 0,
+                },
+            }
+        })
+        .collect()
+}
+
+pub fn load() -> Vec<PathBuf> {
+    visits().into_iter().map(|visit| visit.path).collect()
+}
+
+// The frecency of every remembered entry.
+pub fn frecencies() -> HashMap<PathBuf, u64> {
+    let now = now();
+    visits()
+        .into_iter()
+        .map(|visit| {
+            let frecency = visit.frecency(now);
+            (visit.path, frecency)
+        })
+        .collect()
+}
+
+// Moves an entry to the top of the list, or adds it there, and counts it.
+pub fn remember(path: &Path) -> std::io::Result<()> {
+    let recent_path = recent_path().ok_or(std::io::ErrorKind::NotFound)?;
+    let mut visits = visits();
+    let count = match visits.iter().position(|visit| visit.path == path) {
+        Some(i) => visits.remove(i).count + 1,
+        None => 1,
+    };
+    visits.insert(
+        0,
+        Visit {
+            path: path.to_path_buf(),
+            count,
+            last: now(),
+        },
+    );
+    visits.truncate(MAX_RECENT);
+
+    if let Some(dir) = recent_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let lines: Vec<String> = visits
+        .iter()
+        .map(|visit| {
+            format!(
+                "{}\t{}\t{}",
+                visit.count,
+                visit.last,
+                display_path(&visit.path)
+            )
+        })
+        .collect();
+    std::fs::write(recent_path, lines.join("\n") + "\n")
+}
+
+// The entries picked in earlier sessions below the root. Entries that no
+// longer exist are left out.
+pub fn recent_list(root: &Path) -> JumpList<PathBuf> {
+    JumpList::new(
+        load()
+            .into_iter()
+            .filter(|entry| entry.starts_with(root) && entry.exists())
+            .collect(),
+    )
+}
@@ -1,49 +1,118 @@
+#[cfg(target_os = "linux")]
+use crate::inotify::Inotify;
 use crate::{
-    read_dir_incremental, refresh, ui,
-    util::{term_setup, term_teardown},
-    ColorOptions, TreeNode,
+    annotate::Annotator,
+    config::{config_paths, set_config},
+    content::ContentSearch,
+    events::{emit_matches, emit_scan, emit_selection},
+    fileops::{update_tree, Change, Prompt},
+    git::GitStatuses,
+    i18n::{tr, trf},
+    icons::icon,
+    is_timed_out,
+    jump_list::JumpList,
+    keymap::{describe_key, Action, Chord, Keymap, CHORD_TIMEOUT},
+    ls_colors::LsColors,
+    macros::Macros,
+    palette::{palette, Palette, ThemePicker, DEFAULT_PALETTE},
+    pattern::MatchMode,
+    plugins::{load_plugins, plugin_dir, run_plugin, Annotation, Plugin, PluginAction},
+    portability::{portability_report, Finding},
+    project::{project_list, subtree, Project},
+    read_again, read_dir_incremental, read_level,
+    recent::{frecencies, recent_list},
+    refresh, reload_settings,
+    scaffold::Status,
+    screenshot,
+    scripting::{default_script_path, Script},
+    tags::{Tag, Tags},
+    util::{
+        copy_to_clipboard, display_path, get_tree_count, node_name, ring_bell, set_title,
+        term_setup, term_teardown, window_title,
+    },
+    watch::Watch,
+    ColorOptions, DisplayOptions, NodeType, SortKey, TreeNode, ZeroMatchAlert,
 };
-use crossterm::event::{self, Event, KeyCode};
-use std::{path::PathBuf, time::Duration};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use regex::Regex;
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::debug;
+use tui::{buffer::Buffer, layout::Rect};
 
-pub fn print_tree(root: &TreeNode, indent: &Vec<String>, color_options: &ColorOptions) -> String {
+// Entries are colored by LS_COLORS when it is set and covers them.
+pub fn print_tree(
+    root: &TreeNode,
+    path: &Path,
+    indent: &[String],
+    color_options: &ColorOptions,
+    ls_colors: Option<&LsColors>,
+    icons: bool,
+) -> String {
     let mut return_string = String::new();
-    let mut indent = indent.clone();
+    let mut indent = indent.to_vec();
+    let color = ls_colors
+        .and_then(|ls_colors| ls_colors.codes(path))
+        .map_or(root.color.to_string(), str::to_string);
+    let val = if icons {
+        format!("{} {}", icon(path, root.node_type), root.val)
+    } else {
+        root.val.clone()
+    };
 
-    if indent.len() == 0 {
+    if indent.is_empty() {
         match color_options {
             ColorOptions::Default => {
-                return_string.push_str(&format!("\x1b[{}m", root.color));
-                return_string.push_str(&format!("{}", root.val));
-                return_string.push_str(&format!("\x1b[0m\n"));
+                return_string.push_str(&format!("\x1b[{}m", color));
+                return_string.push_str(&val);
+                return_string.push_str("\x1b[0m\n");
+            }
+            ColorOptions::Depth => {
+                return_string.push_str(&format!("\x1b[38;5;{}m", depth_color(0)));
+                return_string.push_str(&val);
+                return_string.push_str("\x1b[0m\n");
             }
             ColorOptions::NoColor => {
-                return_string.push_str(&format!("{}", root.val));
-                return_string.push_str(&format!("\n"));
+                return_string.push_str(&val);
+                return_string.push('\n');
             }
         }
     } else {
         match color_options {
             ColorOptions::Default => {
                 return_string.push_str(&format!("{}──", indent.join("")));
-                return_string.push_str(&format!("\x1b[{}m", root.color));
-                return_string.push_str(&format!(" {}", root.val));
-                return_string.push_str(&format!("\x1b[0m\n"));
+                return_string.push_str(&format!("\x1b[{}m", color));
+                return_string.push_str(&format!(" {}", val));
+                return_string.push_str("\x1b[0m\n");
+            }
+            ColorOptions::Depth => {
+                return_string.push_str(&format!("\x1b[38;5;{}m", depth_color(indent.len())));
+                return_string.push_str(&format!("{}──", indent.join("")));
+                return_string.push_str(&format!(" {}", val));
+                return_string.push_str("\x1b[0m\n");
             }
             ColorOptions::NoColor => {
                 return_string.push_str(&format!("{}──", indent.join("")));
-                return_string.push_str(&format!(" {}", root.val));
-                return_string.push_str(&format!("\n"));
+                return_string.push_str(&format!(" {}", val));
+                return_string.push('\n');
             }
         }
     }
 
-    if root.children.len() != 0 {
-        if indent.len() > 0 && indent.last().unwrap() == "├" {
+    if !root.children.is_empty() {
+        if !indent.is_empty() && indent.last().unwrap() == "├" {
             indent.pop();
             indent.push("│   ".to_string());
         }
-        if indent.len() > 0 && indent.last().unwrap() == "└" {
+        if !indent.is_empty() && indent.last().unwrap() == "└" {
             indent.pop();
             indent.push("    ".to_string());
         }
@@ -55,57 +124,1557 @@ pub fn print_tree(root: &TreeNode, indent: &Vec<String>, color_options: &ColorOp
             indent.pop();
             indent.push("└".to_string());
         }
-        return_string.push_str(&print_tree(child, &indent, color_options));
+        return_string.push_str(&print_tree(
+            child,
+            &path.join(&child.val),
+            &indent,
+            color_options,
+            ls_colors,
+            icons,
+        ));
     }
 
     return_string
 }
 
-pub fn render(root: &mut TreeNode, dirname: PathBuf) {
-    let mut terminal = term_setup();
+pub struct Line {
+    pub prefix: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub depth: usize,
+    pub node_type: NodeType,
+    pub children: usize,
+    // Number of children hidden because the directory is folded.
+    pub folded: usize,
+    // For a symbol listed under its file, the file and the symbol.
+    pub tag: Option<(PathBuf, Tag)>,
+}
+
+// Flattens the tree into display rows. With a fold level, directories at
+// that depth or deeper are shown folded and their contents are skipped.
+// Directories in `toggled` are shown the other way round.
+pub fn flatten_tree(
+    root: &TreeNode,
+    path: &Path,
+    indent: &[String],
+    fold_level: Option<usize>,
+    toggled: &HashSet<PathBuf>,
+    lines: &mut Vec<Line>,
+) {
+    let mut indent = indent.to_vec();
+    let folded = fold_level.is_some_and(|level| indent.len() >= level) != toggled.contains(path);
+
+    lines.push(Line {
+        prefix: if indent.is_empty() {
+            String::new()
+        } else {
+            format!("{}── ", indent.join(""))
+        },
+        name: root.val.clone(),
+        path: path.to_path_buf(),
+        depth: indent.len(),
+        node_type: root.node_type,
+        children: root.children.len(),
+        folded: if folded { root.children.len() } else { 0 },
+        tag: None,
+    });
+
+    if folded {
+        return;
+    }
+
+    if !root.children.is_empty() {
+        if !indent.is_empty() && indent.last().unwrap() == "├" {
+            indent.pop();
+            indent.push("│   ".to_string());
+        }
+        if !indent.is_empty() && indent.last().unwrap() == "└" {
+            indent.pop();
+            indent.push("    ".to_string());
+        }
+        indent.push("├".to_string());
+    }
+
+    for (i, child) in root.children.iter().enumerate() {
+        if i == root.children.len() - 1 {
+            indent.pop();
+            indent.push("└".to_string());
+        }
+        flatten_tree(
+            child,
+            &path.join(&child.val),
+            &indent,
+            fold_level,
+            toggled,
+            lines,
+        );
+    }
+}
+
+// Adds the symbols matching the pattern under their files, as if they were
+// entries in them. Their paths are made up below the file's, so that a
+// symbol that goes away leaves its file selected.
+pub fn add_symbols(lines: Vec<Line>, tags: &Tags, filter: &Regex) -> Vec<Line> {
+    let mut with_symbols = Vec::new();
+    for line in lines {
+        let symbols = match line.node_type {
+            NodeType::File => tags.matching(&line.path, filter),
+            NodeType::Dir => Vec::new(),
+        };
+        let indent = match (
+            line.prefix.strip_suffix("├── "),
+            line.prefix.strip_suffix("└── "),
+        ) {
+            (Some(indent), _) => format!("{}│   ", indent),
+            (_, Some(indent)) => format!("{}    ", indent),
+            _ => String::new(),
+        };
+        let (path, depth) = (line.path.clone(), line.depth);
+        with_symbols.push(line);
+
+        for (i, tag) in symbols.iter().enumerate() {
+            let branch = if i + 1 == symbols.len() { "└" } else { "├" };
+            with_symbols.push(Line {
+                prefix: format!("{}{}── ", indent, branch),
+                name: tag.display(),
+                path: path.join(format!("{}:{}", tag.name, i)),
+                depth: depth + 1,
+                node_type: NodeType::File,
+                children: 0,
+                folded: 0,
+                tag: Some((path.clone(), (*tag).clone())),
+            });
+        }
+    }
+    with_symbols
+}
+
+pub fn depth_color(depth: usize) -> u8 {
+    DEFAULT_PALETTE.depth_color(depth)
+}
 
-    let content = print_tree(&root, &Vec::new(), &ColorOptions::NoColor);
-    terminal.draw(|f| ui(f, None, Some(content))).unwrap();
+pub struct State {
+    pub dirname: PathBuf,
+    pub search_term: String,
+    pub projects: Option<JumpList<Project>>,
+    pub portability: Option<JumpList<Finding>>,
+    pub recent: Option<JumpList<PathBuf>>,
+    // How often and lately entries were picked in earlier sessions.
+    pub frecency: HashMap<PathBuf, u64>,
+    // The best matches, listed instead of the tree while it is open.
+    pub quick: Option<JumpList<PathBuf>>,
+    pub themes: Option<ThemePicker>,
+    pub budget_report: bool,
+    // The colors in use and the name they were chosen by.
+    pub palette_name: String,
+    pub palette: Palette,
+    // The bindings, which change with the config file.
+    pub keymap: Keymap,
+    pub view_root: Vec<String>,
+    pub scanning: bool,
+    // Entries read so far, for the progress shown while scanning.
+    pub scanned: usize,
+    pub flash: bool,
+    pub selected: usize,
+    pub selected_path: Option<PathBuf>,
+    pub anchor: Option<PathBuf>,
+    pub regex_debug: bool,
+    pub details: bool,
+    pub plugins: Vec<Plugin>,
+    pub annotations: HashMap<PathBuf, Annotation>,
+    pub git: Option<GitStatuses>,
+    pub message: Option<String>,
+    pub script: Option<Script>,
+    pub annotator: Annotator,
+    pub content: ContentSearch,
+    pub content_mode: bool,
+    // Whether the pattern is matched against the symbols in the tags file.
+    pub symbol_mode: bool,
+    pub lines: Vec<Line>,
+    pub hidden: HashSet<PathBuf>,
+    // Whether entries whose names start with a dot are listed.
+    pub show_hidden: bool,
+    pub hide_binaries: bool,
+    // Entries whose names differ from a sibling's only by case.
+    pub case_conflicts: HashSet<PathBuf>,
+    pub match_mode: MatchMode,
+    // Whether only those are listed.
+    pub only_case_conflicts: bool,
+    // Whether chains of directories with one directory in them are shown
+    // as one entry.
+    pub compact: bool,
+    // Whether directories are listed before the files next to them.
+    pub dirs_first: bool,
+    pub show_size: bool,
+    // Depth below which directories are folded, or None to show everything.
+    pub fold_level: Option<usize>,
+    // Directories folded or unfolded by hand, against the fold level.
+    pub toggled: HashSet<PathBuf>,
+    // How deep the tree has been read, or None if it was read completely.
+    pub max_depth: Option<usize>,
+    // A level below max_depth waiting to be read.
+    pub read_level: Option<usize>,
+    // A directory that timed out waiting to be read again.
+    pub retry: Option<PathBuf>,
+    // The row clicked last and when, to tell double clicks.
+    pub last_click: Option<(usize, Instant)>,
+    // The label typed so far while jump mode is active.
+    pub jump: Option<String>,
+    // Entries marked to be printed together, in the order they were marked.
+    pub marked: Vec<PathBuf>,
+    pub sort: SortKey,
+    // Whether the order of entries is turned around.
+    pub reverse: bool,
+    // Modification times of entries, once they have been sorted by them.
+    pub mtimes: HashMap<PathBuf, Option<SystemTime>>,
+    // A file operation waiting for a name or a confirmation.
+    pub prompt: Option<Prompt>,
+    // A file operation done on disk that the tree has yet to follow.
+    pub change: Option<Change>,
+    // First row and number of rows shown by the last draw.
+    pub viewport: Cell<(usize, usize)>,
+    // Rows the tree window had room for in the last draw.
+    pub window_rows: Cell<usize>,
+    // The most recent keys, newest last, while the key debug overlay is
+    // shown.
+    pub key_log: Option<VecDeque<String>>,
+    // An event read after an Esc that turned out not to belong to it.
+    pub pending: Option<Event>,
+    // The screen as it was last drawn.
+    pub frame: Buffer,
+    // In navigation mode typed characters select entries by name instead of
+    // editing the pattern.
+    pub navigating: bool,
+    pub type_ahead: String,
+    pub type_ahead_at: Instant,
+    // Keys held back because they start a chord.
+    pub chord: String,
+    pub chord_at: Instant,
+    pub macros: Macros,
+    // A `q` or `@` waiting for its register, and the count typed before it.
+    pub macro_command: Option<char>,
+    pub count: usize,
+}
 
-    let mut search_term = String::new();
+impl State {
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.lines.len().saturating_sub(1));
+        self.selected_path = self.lines.get(self.selected).map(|line| line.path.clone());
+    }
 
-    let mut running = true;
-    let mut duration = 0;
-    loop {
-        if running {
-            let mut allocated = 100;
-            read_dir_incremental(root, dirname.clone(), &mut allocated);
+    // Selects a row on behalf of the user, making it the entry that is kept
+    // selected across refreshes.
+    pub fn move_selection(&mut self, index: usize) {
+        self.select(index);
+        self.anchor = self.selected_path.clone();
+    }
+
+    // The row of the directory containing the selected entry.
+    pub fn parent(&self) -> Option<usize> {
+        let depth = self.lines.get(self.selected)?.depth;
+        (0..self.selected)
+            .rev()
+            .find(|&i| self.lines[i].depth < depth)
+    }
+
+    // The rows of the entries in the same directory as the selected one.
+    pub fn siblings(&self) -> Vec<usize> {
+        let depth = match self.lines.get(self.selected) {
+            Some(line) => line.depth,
+            None => return Vec::new(),
+        };
+        let start = self.parent().map_or(0, |parent| parent + 1);
+
+        (start..self.lines.len())
+            .take_while(|&i| self.lines[i].depth >= depth)
+            .filter(|&i| self.lines[i].depth == depth)
+            .collect()
+    }
+
+    pub fn first_child(&self) -> Option<usize> {
+        let depth = self.lines.get(self.selected)?.depth;
+        let next = self.selected + 1;
+        self.lines
+            .get(next)
+            .filter(|line| line.depth > depth)
+            .map(|_| next)
+    }
+}
+
+// Fails without touching the tree if the terminal can't be set up.
+pub fn render(
+    root: &mut TreeNode,
+    dirname: PathBuf,
+    pattern: String,
+    options: &DisplayOptions,
+) -> io::Result<Outcome> {
+    let (script, mut message) = match load_script(options) {
+        Ok(script) => (script, None),
+        Err(e) => (None, Some(e)),
+    };
+    let mut annotations = HashMap::new();
+    if let Some(scaffold) = &options.scaffold {
+        annotations = scaffold.annotations(&dirname);
+        let new = scaffold.count(&dirname, Status::New);
+        message = message.or_else(|| Some(trf("scaffold_prompt", &[&new])));
+    }
+    let mut watch = Watch::new(
+        [
+            options.script.clone().or_else(default_script_path),
+            plugin_dir(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect(),
+    );
+    let mut config_watch = Watch::new(config_paths());
+    let mut columns = options.columns.clone();
+
+    let mut terminal = term_setup(options.mouse)?;
+
+    let mut state = State {
+        dirname: dirname.clone(),
+        search_term: pattern,
+        projects: None,
+        portability: None,
+        recent: None,
+        frecency: frecencies(),
+        quick: None,
+        themes: None,
+        budget_report: false,
+        palette_name: options.palette.clone(),
+        palette: palette(&options.palette).unwrap_or(DEFAULT_PALETTE),
+        keymap: options.keymap.clone(),
+        view_root: Vec::new(),
+        scanning: !options.from_stdin && options.scaffold.is_none(),
+        scanned: 0,
+        flash: false,
+        selected: 0,
+        selected_path: None,
+        anchor: None,
+        regex_debug: options.regex_debug,
+        details: false,
+        plugins: load_plugins(),
+        annotations,
+        git: if options.git {
+            GitStatuses::load(&dirname)
+        } else {
+            None
+        },
+        message,
+        script,
+        annotator: Annotator::new(&options.columns, options.tuning.workers),
+        content: ContentSearch::new(options.tuning.workers),
+        content_mode: options.content,
+        symbol_mode: false,
+        lines: Vec::new(),
+        hidden: HashSet::new(),
+        show_hidden: options.show_hidden,
+        hide_binaries: options.hide_binaries,
+        case_conflicts: HashSet::new(),
+        match_mode: options.match_mode,
+        only_case_conflicts: options.only_case_conflicts,
+        compact: options.compact,
+        dirs_first: options.dirs_first,
+        show_size: options.show_size,
+        fold_level: options.fold_level,
+        toggled: HashSet::new(),
+        max_depth: options.max_depth,
+        read_level: None,
+        retry: None,
+        last_click: None,
+        jump: None,
+        marked: Vec::new(),
+        sort: options.sort.clone(),
+        reverse: options.reverse,
+        mtimes: HashMap::new(),
+        prompt: None,
+        change: None,
+        viewport: Cell::new((0, 0)),
+        window_rows: Cell::new(0),
+        key_log: options.key_debug.then(VecDeque::new),
+        pending: None,
+        frame: Buffer::empty(Rect::default()),
+        navigating: options.vim,
+        type_ahead: String::new(),
+        type_ahead_at: Instant::now(),
+        chord: String::new(),
+        chord_at: Instant::now(),
+        macros: Macros::default(),
+        macro_command: None,
+        count: 0,
+    };
+
+    #[cfg(target_os = "linux")]
+    let mut inotify = match options.watch.then(Inotify::new) {
+        Some(Ok(mut inotify)) => {
+            if !state.scanning {
+                inotify.watch(root, &dirname);
+            }
+            Some(inotify)
+        }
+        Some(Err(e)) => {
+            state.message = Some(trf("watch_failed", &[&e]));
+            None
+        }
+        None => None,
+    };
+
+    let mut matches = refresh(root, &mut state, options, &mut terminal);
+    let mut title = String::new();
+    let mut emitted_pattern = None;
+    let mut emitted_selection = None;
+    let mut hooked_selection = None;
+
+    // While scanning input is only checked between chunks. Afterwards the
+    // loop waits for input up to the tick, so background updates such as
+    // column values are drawn even when no key is pressed.
+    let mut timeout = Duration::ZERO;
+    let outcome = loop {
+        let mut redraw = false;
+
+        if state.scanning {
+            let mut allocated = options.tuning.chunk;
+            let started = Instant::now();
+            read_dir_incremental(
+                root,
+                dirname.clone(),
+                &mut allocated,
+                state.max_depth,
+                options.backend,
+            );
+            let read = options.tuning.chunk - allocated.max(0);
+            state.scanned += read as usize;
 
             if allocated > 0 {
-                running = false;
-                duration = 10;
-            }
-            refresh(&root, search_term.clone(), &mut terminal);
-        }
-
-        if let Ok(event) = event::poll(Duration::from_millis(duration)) {
-            if event {
-                if let Ok(event) = event::read() {
-                    if let Event::Key(key) = event {
-                        match key.code {
-                            KeyCode::Char(c) => {
-                                search_term.push(c);
-                                refresh(&root, search_term.clone(), &mut terminal);
-                            }
-                            KeyCode::Esc => {
-                                break;
-                            }
-                            KeyCode::Backspace => {
-                                search_term.pop();
-                                refresh(&root, search_term.clone(), &mut terminal);
-                            }
-                            _ => {}
+                state.scanning = false;
+                timeout = options.tick;
+
+                // Directories are watched once they are scanned.
+                #[cfg(target_os = "linux")]
+                if let Some(inotify) = inotify.as_mut() {
+                    inotify.watch(root, &dirname);
+                }
+            }
+            debug!(
+                entries = read,
+                done = !state.scanning,
+                elapsed = ?started.elapsed(),
+                "scanned chunk"
+            );
+            redraw = true;
+
+            if options.emit_events {
+                let entries =
+                    get_tree_count(root, NodeType::File) + get_tree_count(root, NodeType::Dir);
+                emit_scan(entries, !state.scanning);
+            }
+        }
+
+        if let Some(depth) = state.read_level.take() {
+            let started = Instant::now();
+            read_level(root, &dirname, depth, options.backend);
+            debug!(depth, elapsed = ?started.elapsed(), "read level");
+            redraw = true;
+
+            #[cfg(target_os = "linux")]
+            if let Some(inotify) = inotify.as_mut() {
+                inotify.watch(root, &dirname);
+            }
+        }
+
+        if let Some(path) = state.retry.take() {
+            read_again(root, &dirname, &path, state.max_depth, options.backend);
+            state.message = is_timed_out(&path).then(|| tr("timed_out_again"));
+            state
+                .mtimes
+                .retain(|mtime_path, _| !mtime_path.starts_with(&path));
+            redraw = true;
+
+            #[cfg(target_os = "linux")]
+            if let Some(inotify) = inotify.as_mut() {
+                inotify.watch_below(root, &dirname, &path);
+            }
+        }
+
+        // New directories are read and watched as they appear.
+        #[cfg(target_os = "linux")]
+        if let Some(inotify) = inotify.as_mut() {
+            for change in inotify.changes() {
+                update_tree(root, &dirname, &change);
+                if let Change::Created(path, NodeType::Dir) = &change {
+                    read_again(root, &dirname, path, state.max_depth, options.backend);
+                    inotify.watch_below(root, &dirname, path);
+                }
+                redraw = true;
+            }
+            let changed: HashSet<PathBuf> = inotify.changed(&dirname).into_iter().collect();
+            for dir in &changed {
+                state.annotator.forget(dir);
+                redraw = true;
+            }
+            // The modification times of entries in them are read again for
+            // --sort mtime.
+            state
+                .mtimes
+                .retain(|path, _| !path.parent().is_some_and(|dir| changed.contains(dir)));
+        }
+
+        if state.annotator.poll() {
+            redraw = true;
+        }
+
+        if state.content.poll() {
+            redraw = true;
+        }
+
+        // A script that fails to load is reported and the previous one is
+        // kept.
+        if watch.changed() {
+            match load_script(options) {
+                Ok(script) => {
+                    state.script = script;
+                    state.message = Some(tr("reloaded"));
+                }
+                Err(e) => state.message = Some(e),
+            }
+            state.plugins = load_plugins();
+            redraw = true;
+        }
+
+        // The same goes for the config file: palette, bindings and columns
+        // follow it, and a file that doesn't parse keeps the previous ones.
+        if config_watch.changed() {
+            match reload_settings() {
+                Ok(settings) => {
+                    set_palette(&mut state, &settings.palette);
+                    state.keymap = settings.keymap;
+                    if settings.columns != columns {
+                        state.annotator = Annotator::new(&settings.columns, options.tuning.workers);
+                        columns = settings.columns;
+                    }
+                    state.message = Some(tr("config_reloaded"));
+                }
+                Err(e) => state.message = Some(e),
+            }
+            redraw = true;
+        }
+
+        // Keys queued by a macro are handled before new input.
+        let key = match state.macros.next_key() {
+            Some(key) => {
+                debug!(?key, "replayed key");
+                Some(key)
+            }
+            None => match next_event(&mut state.pending, timeout) {
+                Some(Event::Key(key)) => {
+                    debug!(?key, "key");
+                    if let Some(log) = state.key_log.as_mut() {
+                        log.push_back(describe_key(&key));
+                        if log.len() > KEY_LOG_LENGTH {
+                            log.pop_front();
                         }
                     }
+                    state.macros.record(key);
+                    Some(key)
+                }
+                Some(Event::Paste(text)) => {
+                    debug!(length = text.len(), "pasted");
+                    paste(&mut state, &text);
+                    redraw = true;
+                    None
+                }
+                Some(Event::Mouse(mouse)) => {
+                    if let Some(outcome) = handle_mouse(&mut state, options, mouse) {
+                        break outcome;
+                    }
+                    redraw = true;
+                    None
+                }
+                Some(Event::Resize(width, height)) => {
+                    debug!(width, height, "resized");
+                    redraw = true;
+                    None
+                }
+                Some(event) => {
+                    debug!(?event, "ignored event");
+                    None
+                }
+                None => None,
+            },
+        };
+
+        if let Some(key) = key {
+            redraw = true;
+            state.message = None;
+
+            if let Some(outcome) = handle_key(root, &mut state, options, key) {
+                break outcome;
+            }
+            if let Some(change) = state.change.take() {
+                update_tree(root, &dirname, &change);
+            }
+        }
+
+        let new_title = window_title(
+            &display_path(&view_path(&dirname, &state.view_root)),
+            &state.search_term,
+        );
+        if new_title != title {
+            set_title(&new_title);
+            title = new_title;
+        }
+
+        if redraw {
+            let previous_matches = matches;
+            matches = refresh(root, &mut state, options, &mut terminal);
+
+            // Only alert when a pattern edit makes the matches disappear, not
+            // on every redraw while the pattern stays unmatched.
+            if matches == 0 && previous_matches != 0 && !state.search_term.is_empty() {
+                match options.zero_match_alert {
+                    ZeroMatchAlert::Bell => ring_bell(),
+                    ZeroMatchAlert::Flash => {
+                        state.flash = true;
+                        refresh(root, &mut state, options, &mut terminal);
+                        std::thread::sleep(FLASH_DURATION);
+                        state.flash = false;
+                        refresh(root, &mut state, options, &mut terminal);
+                    }
+                    ZeroMatchAlert::None => {}
+                }
+            }
+
+            if hooked_selection != state.selected_path {
+                if let (Some(script), Some(path)) = (&state.script, &state.selected_path) {
+                    if let Some(message) = script.on_select(path) {
+                        state.message = Some(message);
+                        refresh(root, &mut state, options, &mut terminal);
+                    }
+                }
+                hooked_selection = state.selected_path.clone();
+            }
+
+            if options.emit_events {
+                if emitted_pattern.as_ref() != Some(&state.search_term) {
+                    emit_matches(&state.search_term, matches);
+                    emitted_pattern = Some(state.search_term.clone());
+                }
+                if emitted_selection != state.selected_path {
+                    if let Some(path) = &state.selected_path {
+                        emit_selection(path);
+                    }
+                    emitted_selection = state.selected_path.clone();
                 }
             }
         }
+    };
+
+    term_teardown(&mut terminal, options.mouse);
+    Ok(outcome)
+}
+
+// Jump labels are drawn from the home row first so the most common ones
+// are the easiest to type.
+pub const JUMP_ALPHABET: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+// The two letter label of the nth visible row in jump mode.
+pub fn jump_label(index: usize) -> String {
+    let letters: Vec<char> = JUMP_ALPHABET.chars().collect();
+    let first = letters[index % letters.len()];
+    let second = letters[index / letters.len() % letters.len()];
+    format!("{}{}", first, second)
+}
+
+// An explicitly requested script has to exist, the default one doesn't.
+fn load_script(options: &DisplayOptions) -> Result<Option<Script>, String> {
+    match options
+        .script
+        .clone()
+        .or_else(|| default_script_path().filter(|path| path.exists()))
+    {
+        Some(path) => Script::load(&path).map(Some),
+        None => Ok(None),
     }
+}
+
+// How the TUI was left.
+pub enum Outcome {
+    // Enter was pressed on this entry.
+    Selected(PathBuf),
+    // Enter was pressed with these entries marked.
+    Marked(Vec<PathBuf>),
+    // Enter was pressed on a symbol, which is in the file at the line.
+    Symbol(PathBuf, Option<usize>),
+    Aborted,
+}
+
+// Handles a key press. Returns how to leave the TUI when the key does.
+fn handle_key(
+    root: &TreeNode,
+    state: &mut State,
+    options: &DisplayOptions,
+    key: KeyEvent,
+) -> Option<Outcome> {
+    if let Some(project_list) = state.projects.as_mut() {
+        match key.code {
+            KeyCode::Up => project_list.select_previous(),
+            KeyCode::Down => project_list.select_next(),
+            KeyCode::Enter => {
+                if let Some(project) = project_list.selected() {
+                    state.view_root = project.path.clone();
+                }
+                state.projects = None;
+            }
+            KeyCode::Esc => state.projects = None,
+            _ => {}
+        }
+    } else if let Some(report) = state.portability.as_mut() {
+        match key.code {
+            KeyCode::Up => report.select_previous(),
+            KeyCode::Down => report.select_next(),
+            KeyCode::Enter => {
+                state.anchor = report.selected().map(|finding| finding.path.clone());
+                state.portability = None;
+            }
+            KeyCode::Esc => state.portability = None,
+            _ => {}
+        }
+    } else if let Some(recent) = state.recent.as_mut() {
+        match key.code {
+            KeyCode::Up => recent.select_previous(),
+            KeyCode::Down => recent.select_next(),
+            KeyCode::Enter => {
+                if let Some(path) = recent.selected().cloned() {
+                    reveal(state, &path);
+                }
+                state.recent = None;
+            }
+            KeyCode::Esc => state.recent = None,
+            _ => {}
+        }
+    } else if let Some(quick) = state.quick.as_mut() {
+        match key.code {
+            KeyCode::Up => quick.select_previous(),
+            KeyCode::Down => quick.select_next(),
+            KeyCode::Enter => {
+                if let Some(path) = quick.selected() {
+                    return Some(Outcome::Selected(path.clone()));
+                }
+            }
+            KeyCode::Esc => state.quick = None,
+            KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.quick = None;
+            }
+            KeyCode::Char(_) if key.modifiers.contains(KeyModifiers::CONTROL) => {}
+            KeyCode::Char(c) => state.search_term.push(c),
+            KeyCode::Backspace => {
+                state.search_term.pop();
+            }
+            _ => {}
+        }
+    } else if let Some(picker) = state.themes.as_mut() {
+        match key.code {
+            KeyCode::Up => picker.list.select_previous(),
+            KeyCode::Down => picker.list.select_next(),
+            KeyCode::Enter => {
+                state.themes = None;
+                state.message = Some(match set_config("palette", &state.palette_name) {
+                    Ok(path) => trf("saved_theme", &[&display_path(&path)]),
+                    Err(e) => trf("theme_failed", &[&e]),
+                });
+                return None;
+            }
+            KeyCode::Esc => {
+                let original = picker.original.clone();
+                state.themes = None;
+                set_palette(state, &original);
+                return None;
+            }
+            _ => return None,
+        }
+        if let Some(name) = picker.list.selected().copied() {
+            set_palette(state, name);
+        }
+    } else if let Some(prompt) = state.prompt.as_mut() {
+        match (key.code, prompt.input()) {
+            (KeyCode::Char('y'), None) | (KeyCode::Enter, Some(_)) => run_prompt(state),
+            (KeyCode::Char(c), Some(name)) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                name.push(c);
+            }
+            (KeyCode::Backspace, Some(name)) => {
+                name.pop();
+            }
+            (KeyCode::Esc, _) | (_, None) => state.prompt = None,
+            _ => {}
+        }
+    } else if let Some(typed) = state.jump.as_mut() {
+        match key.code {
+            KeyCode::Char(c) if JUMP_ALPHABET.contains(c) => {
+                typed.push(c);
+                if typed.chars().count() == 2 {
+                    let (scroll, rows) = state.viewport.get();
+                    let target = (0..rows).find(|&i| jump_label(i) == *typed);
+                    state.jump = None;
+                    if let Some(i) = target {
+                        state.move_selection(scroll + i);
+                    }
+                }
+            }
+            _ => state.jump = None,
+        }
+    } else if let Some(action) = state.keymap.key(&key) {
+        return run_action(state, options, action);
+    } else {
+        match key.code {
+            KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.jump = Some(String::new());
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.projects = Some(project_list(root));
+            }
+            KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.quick = Some(JumpList::default());
+            }
+            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.recent = Some(recent_list(&state.dirname));
+            }
+            KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.portability = Some(portability_report(root, &state.dirname));
+            }
+            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.budget_report = !state.budget_report;
+            }
+            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.themes = Some(ThemePicker::new(&state.palette_name));
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.regex_debug = !state.regex_debug;
+            }
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.details = !state.details;
+            }
+            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                unhide_all(state);
+            }
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                toggle_match_mode(state);
+            }
+            KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                toggle_search_target(state, options);
+            }
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                state.show_size = !state.show_size;
+            }
+            KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                toggle_dotfiles(state);
+            }
+            KeyCode::F(12) => {
+                state.key_log = match state.key_log {
+                    Some(_) => None,
+                    None => Some(VecDeque::new()),
+                };
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(Outcome::Aborted);
+            }
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                save_screenshot(state, options);
+            }
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let rows = (page_rows(state, options) / 2).max(1);
+                state.move_selection(state.selected + rows);
+            }
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let rows = (page_rows(state, options) / 2).max(1);
+                state.move_selection(state.selected.saturating_sub(rows));
+            }
+            KeyCode::PageDown => return run_action(state, options, Action::PageDown),
+            KeyCode::PageUp => return run_action(state, options, Action::PageUp),
+            KeyCode::Delete => hide_selected(state),
+            // A new fold level applies to all directories alike.
+            KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) && c.is_ascii_digit() => {
+                state.fold_level = c.to_digit(10).map(|level| level as usize);
+                state.toggled.clear();
+            }
+            KeyCode::Char('*') if key.modifiers.contains(KeyModifiers::ALT) => {
+                state.fold_level = None;
+                state.toggled.clear();
+            }
+            KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => {
+                trigger_plugin(state, c);
+            }
+            KeyCode::Tab => state.navigating = !state.navigating,
+            // Unbound Ctrl combinations aren't typed.
+            KeyCode::Char(_) if key.modifiers.contains(KeyModifiers::CONTROL) => {}
+            KeyCode::Char(c) if state.navigating => {
+                return navigation_key(state, options, c);
+            }
+            KeyCode::Esc if state.navigating && !options.vim => state.navigating = false,
+            KeyCode::Esc if options.vim && !state.navigating => state.navigating = true,
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => {
+                let siblings = state.siblings();
+                if let Some(&i) = siblings.iter().rev().find(|&&i| i < state.selected) {
+                    state.move_selection(i);
+                }
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                let siblings = state.siblings();
+                if let Some(&i) = siblings.iter().find(|&&i| i > state.selected) {
+                    state.move_selection(i);
+                }
+            }
+            KeyCode::Home => {
+                if let Some(&i) = state.siblings().first() {
+                    state.move_selection(i);
+                }
+            }
+            KeyCode::End => {
+                if let Some(&i) = state.siblings().last() {
+                    state.move_selection(i);
+                }
+            }
+            KeyCode::Left => {
+                if let Some(i) = state.parent() {
+                    state.move_selection(i);
+                }
+            }
+            KeyCode::Right => {
+                if let Some(i) = state.first_child() {
+                    state.move_selection(i);
+                }
+            }
+            KeyCode::Up => {
+                state.move_selection(state.selected.saturating_sub(1));
+            }
+            KeyCode::Down => {
+                state.move_selection(state.selected + 1);
+            }
+            KeyCode::Char(c) => {
+                state.search_term.push(c);
+            }
+            // Esc clears the pattern before it quits.
+            KeyCode::Esc if !state.search_term.is_empty() => state.search_term.clear(),
+            KeyCode::Esc => {
+                return Some(Outcome::Aborted);
+            }
+            KeyCode::Backspace => {
+                state.search_term.pop();
+            }
+            KeyCode::Enter => return pick(state),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+// Rows the selection moves for each step of the wheel.
+const WHEEL_ROWS: usize = 3;
+
+// Clicking a row selects it, and clicking it again right away folds or
+// unfolds a directory and picks a file like Enter does. The wheel moves the
+// selection, which the view follows. Overlays take no mouse input.
+fn handle_mouse(state: &mut State, options: &DisplayOptions, mouse: MouseEvent) -> Option<Outcome> {
+    if state.projects.is_some()
+        || state.portability.is_some()
+        || state.quick.is_some()
+        || state.recent.is_some()
+        || state.themes.is_some()
+        || state.prompt.is_some()
+    {
+        return None;
+    }
+
+    match mouse.kind {
+        MouseEventKind::ScrollDown => state.move_selection(state.selected + WHEEL_ROWS),
+        MouseEventKind::ScrollUp => {
+            state.move_selection(state.selected.saturating_sub(WHEEL_ROWS));
+        }
+        MouseEventKind::Down(MouseButton::Left) => {
+            // The rows start below the border of the tree window.
+            let top = usize::from(!options.screen_reader);
+            let (scroll, rows) = state.viewport.get();
+            let row = (mouse.row as usize)
+                .checked_sub(top)
+                .filter(|&row| row < rows)?;
+            let index = scroll + row;
+            let double = state
+                .last_click
+                .is_some_and(|(last, at)| last == index && at.elapsed() < DOUBLE_CLICK_TIME);
+            state.move_selection(index);
+
+            if !double {
+                state.last_click = Some((index, Instant::now()));
+                return None;
+            }
+            state.last_click = None;
+            match state.lines.get(index).map(|line| line.node_type) {
+                Some(NodeType::Dir) => toggle_fold(state),
+                Some(NodeType::File) => return pick(state),
+                None => {}
+            }
+        }
+        _ => {}
+    }
+    None
+}
+
+fn set_palette(state: &mut State, name: &str) {
+    if let Some(palette) = palette(name) {
+        state.palette = palette;
+        state.palette_name = name.to_string();
+    }
+}
+
+fn save_screenshot(state: &mut State, options: &DisplayOptions) {
+    let path = options.screenshot.clone().unwrap_or_else(|| {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        PathBuf::from(format!("tree-rs-{}.ans", now))
+    });
+    state.message = Some(match screenshot::save(&state.frame, &path) {
+        Ok(()) => trf("saved_screenshot", &[&display_path(&path)]),
+        Err(e) => trf("screenshot_failed", &[&display_path(&path), &e]),
+    });
+}
+
+// Pasted text is added to the pattern as a whole instead of being handled
+// as keys, so a pasted regex doesn't trigger bindings. Line breaks and other
+// control characters are dropped.
+fn paste(state: &mut State, text: &str) {
+    if let Some(name) = state.prompt.as_mut().and_then(Prompt::input) {
+        name.extend(text.chars().filter(|c| !c.is_control()));
+        return;
+    }
+    if state.projects.is_some()
+        || state.portability.is_some()
+        || state.themes.is_some()
+        || state.jump.is_some()
+    {
+        return;
+    }
+    state.navigating = false;
+    state
+        .search_term
+        .extend(text.chars().filter(|c| !c.is_control()));
+}
+
+// How long a key may follow Esc to be taken as Alt+key. Typing the two
+// keys one after the other takes far longer.
+const ESC_TIMEOUT: Duration = Duration::from_millis(30);
+
+// The next input event, waiting at most timeout. Terminals send Alt+key as
+// Esc followed by the key, which crossterm only joins when both arrive in
+// the same read. Over slow connections they can arrive apart, so a key
+// following a lone Esc within ESC_TIMEOUT is taken as Alt+key. Anything
+// else is kept for the next call.
+fn next_event(pending: &mut Option<Event>, timeout: Duration) -> Option<Event> {
+    let event = pending.take().or_else(|| read_event(timeout))?;
+    if let Event::Key(key) = &event {
+        if key.code == KeyCode::Esc && key.modifiers.is_empty() {
+            match read_event(ESC_TIMEOUT) {
+                Some(Event::Key(next))
+                    if matches!(next.code, KeyCode::Char(_))
+                        && !next.modifiers.contains(KeyModifiers::ALT) =>
+                {
+                    return Some(Event::Key(KeyEvent::new(
+                        next.code,
+                        next.modifiers | KeyModifiers::ALT,
+                    )));
+                }
+                next => *pending = next,
+            }
+        }
+    }
+    Some(event)
+}
+
+// Windows consoles report releasing a key as well as pressing it, which
+// would handle every key twice, so releases are dropped.
+fn read_event(timeout: Duration) -> Option<Event> {
+    match event::poll(timeout) {
+        Ok(true) => match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Release => None,
+            Ok(event) => Some(event),
+            Err(e) => {
+                debug!(error = %e, "reading input failed");
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+// Keys shown by the key debug overlay.
+pub const KEY_LOG_LENGTH: usize = 8;
+
+// Rows PageUp and PageDown move, the height of the tree window unless set
+// with --page-size.
+fn page_rows(state: &State, options: &DisplayOptions) -> usize {
+    options
+        .page_size
+        .unwrap_or_else(|| state.window_rows.get())
+        .max(1)
+}
+
+// Handles a character typed in navigation mode, running the chord it
+// completes or falling back to type-ahead.
+fn navigation_key(state: &mut State, options: &DisplayOptions, c: char) -> Option<Outcome> {
+    if let Some(command) = state.macro_command.take() {
+        let count = std::mem::take(&mut state.count).max(1);
+        if command == 'q' {
+            state.macros.start(c);
+        } else if !state.macros.replay(c, count) {
+            state.message = Some(trf("no_macro", &[&c]));
+        }
+        return None;
+    }
+
+    if c == 'q' && state.macros.recording().is_some() {
+        state.macros.stop();
+        return None;
+    }
+    if c == 'q' || c == '@' {
+        state.macro_command = Some(c);
+        return None;
+    }
+    if let Some(digit) = c.to_digit(10).filter(|&d| d > 0 || state.count > 0) {
+        state.count = state.count * 10 + digit as usize;
+        return None;
+    }
+    state.count = 0;
+
+    if state.chord_at.elapsed() > CHORD_TIMEOUT {
+        state.chord.clear();
+    }
+    state.chord.push(c);
+    state.chord_at = Instant::now();
+
+    match state.keymap.lookup(&state.chord) {
+        Chord::Complete(action) => {
+            state.chord.clear();
+            return run_action(state, options, action);
+        }
+        Chord::Partial => {}
+        Chord::Unbound => {
+            for c in std::mem::take(&mut state.chord).chars() {
+                type_ahead(state, c);
+            }
+        }
+    }
+    None
+}
+
+// Returns how to leave the TUI when the action does.
+fn run_action(state: &mut State, options: &DisplayOptions, action: Action) -> Option<Outcome> {
+    match action {
+        Action::Up => state.move_selection(state.selected.saturating_sub(1)),
+        Action::Down => state.move_selection(state.selected + 1),
+        Action::Top => state.move_selection(0),
+        Action::Bottom => state.move_selection(state.lines.len().saturating_sub(1)),
+        Action::Parent => {
+            if let Some(i) = state.parent() {
+                state.move_selection(i);
+            }
+        }
+        Action::Hide => hide_selected(state),
+        Action::Yank => {
+            // A symbol's file is copied instead of the symbol.
+            let path = match state.lines.get(state.selected) {
+                Some(Line {
+                    tag: Some((file, _)),
+                    ..
+                }) => Some(file),
+                _ => state.selected_path.as_ref(),
+            };
+            if let Some(path) = path {
+                let path = display_path(path);
+                copy_to_clipboard(&path);
+                state.message = Some(trf("copied", &[&path]));
+            }
+        }
+        Action::Jump => state.jump = Some(String::new()),
+        Action::Delete => {
+            if let Some(path) = operated_path(state) {
+                state.prompt = Some(Prompt::Delete(path));
+            }
+        }
+        Action::Rename => {
+            if let Some(path) = operated_path(state) {
+                let name = node_name(&path);
+                state.prompt = Some(Prompt::Rename(path, name));
+            }
+        }
+        Action::Create => {
+            // New entries go into the selected directory, or next to the
+            // selected file.
+            let dir = match state.lines.get(state.selected) {
+                Some(line) if line.node_type == NodeType::Dir && line.tag.is_none() => {
+                    Some(line.path.clone())
+                }
+                Some(line) if line.tag.is_none() => line.path.parent().map(Path::to_path_buf),
+                _ => None,
+            };
+            if let Some(dir) = dir {
+                state.prompt = Some(Prompt::Create(dir, String::new()));
+            }
+        }
+        Action::Fold => toggle_fold(state),
+        Action::Mark => toggle_mark(state),
+        Action::Sort => {
+            state.sort = state.sort.next();
+            state.message = Some(trf("sorted_by", &[&state.sort.name()]));
+        }
+        Action::Reverse => {
+            state.reverse = !state.reverse;
+            state.message = Some(tr(if state.reverse {
+                "reversed"
+            } else {
+                "not_reversed"
+            }));
+        }
+        Action::Dotfiles => toggle_dotfiles(state),
+        Action::Deeper => deeper(state),
+        Action::Shallower => shallower(state),
+        Action::Retry => retry(state),
+        Action::Conflicts => toggle_case_conflicts(state),
+        Action::Binaries => {
+            state.hide_binaries = !state.hide_binaries;
+            state.message = Some(tr(if state.hide_binaries {
+                "hiding_binaries"
+            } else {
+                "showing_binaries"
+            }));
+        }
+        Action::Compact => {
+            state.compact = !state.compact;
+            state.message = Some(tr(if state.compact {
+                "compact_dirs"
+            } else {
+                "expanded_dirs"
+            }));
+        }
+        Action::DirsFirst => {
+            state.dirs_first = !state.dirs_first;
+            state.message = Some(tr(if state.dirs_first {
+                "dirs_first"
+            } else {
+                "dirs_mixed"
+            }));
+        }
+        Action::Collapse => collapse(state),
+        Action::Expand => expand(state),
+        Action::Search => state.navigating = false,
+        Action::PageUp => {
+            let rows = page_rows(state, options);
+            state.move_selection(state.selected.saturating_sub(rows));
+        }
+        Action::PageDown => {
+            let rows = page_rows(state, options);
+            state.move_selection(state.selected + rows);
+        }
+        Action::Select => return pick(state),
+        Action::Clear => state.search_term.clear(),
+        Action::Quit => return Some(Outcome::Aborted),
+        Action::None => {}
+    }
+    None
+}
+
+// The selected entry, unless it is the root or a symbol, which files can't
+// be operated on as.
+fn operated_path(state: &State) -> Option<PathBuf> {
+    state
+        .lines
+        .get(state.selected)
+        .filter(|line| line.tag.is_none() && line.path != state.dirname)
+        .map(|line| line.path.clone())
+}
+
+// Carries out the file operation of the prompt and leaves the tree to be
+// changed to match.
+fn run_prompt(state: &mut State) {
+    let prompt = match state.prompt.take() {
+        Some(prompt) => prompt,
+        None => return,
+    };
+    match prompt.run() {
+        Ok(change) => {
+            state.message = Some(change.describe());
+            state.anchor = change.selected();
+            state.change = Some(change);
+        }
+        Err(e) => state.message = Some(trf("file_op_failed", &[&e])),
+    }
+}
+
+// Reads the selected directory again if it timed out. The message stays up
+// while it is read.
+fn retry(state: &mut State) {
+    match &state.selected_path {
+        Some(path) if is_timed_out(path) => {
+            state.message = Some(trf("retrying", &[&display_path(path)]));
+            state.retry = Some(path.clone());
+        }
+        _ => state.message = Some(tr("not_timed_out")),
+    }
+}
+
+// Shows one more level. Past the depth that has been read, the next level
+// is read first.
+fn deeper(state: &mut State) {
+    state.fold_level = state.fold_level.map(|level| level + 1);
+    state.toggled.clear();
+
+    if let Some(depth) = state.max_depth {
+        if state.fold_level.is_none_or(|level| level > depth) {
+            state.max_depth = Some(depth + 1);
+            state.read_level = Some(depth);
+        }
+    }
+}
+
+// Shows one level less. The levels stay read and come back with +.
+fn shallower(state: &mut State) {
+    let depth = state
+        .fold_level
+        .or(state.max_depth)
+        .unwrap_or_else(|| state.lines.iter().map(|line| line.depth).max().unwrap_or(0));
+    state.fold_level = Some(depth.saturating_sub(1));
+    state.toggled.clear();
+}
+
+// Selects an entry, unfolding the directories leading to it. The pattern
+// is cleared and the whole tree shown if either would hide it.
+fn reveal(state: &mut State, path: &Path) {
+    if !path.starts_with(view_path(&state.dirname, &state.view_root)) {
+        state.view_root.clear();
+    }
+    let root = view_path(&state.dirname, &state.view_root);
+    for dir in path.ancestors().skip(1) {
+        let depth = match dir.strip_prefix(&root) {
+            Ok(relative) => relative.components().count(),
+            Err(_) => break,
+        };
+        let folded =
+            state.fold_level.is_some_and(|level| depth >= level) != state.toggled.contains(dir);
+        if folded && !state.toggled.remove(dir) {
+            state.toggled.insert(dir.to_path_buf());
+        }
+    }
+    state.search_term.clear();
+    state.anchor = Some(path.to_path_buf());
+}
+
+// Marks the selected entry, or unmarks it if it is marked. Symbols can't be
+// marked.
+fn toggle_mark(state: &mut State) {
+    let path = match state.lines.get(state.selected) {
+        Some(line) if line.tag.is_none() => line.path.clone(),
+        _ => return,
+    };
+    match state.marked.iter().position(|marked| *marked == path) {
+        Some(i) => {
+            state.marked.remove(i);
+        }
+        None => state.marked.push(path),
+    }
+    state.message = Some(trf("marked", &[&state.marked.len()]));
+}
+
+// Folds the selected directory, or unfolds it if it is folded.
+fn toggle_fold(state: &mut State) {
+    let line = match state.lines.get(state.selected) {
+        Some(line) if line.node_type == NodeType::Dir => line,
+        _ => return,
+    };
+    if !state.toggled.remove(&line.path) {
+        state.toggled.insert(line.path.clone());
+    }
+}
+
+// Folds the selected directory, or selects the parent if there is nothing
+// to fold, like h in file managers with vim keys.
+fn collapse(state: &mut State) {
+    match state.lines.get(state.selected) {
+        Some(line) if line.node_type == NodeType::Dir && state.first_child().is_some() => {
+            toggle_fold(state)
+        }
+        _ => {
+            if let Some(i) = state.parent() {
+                state.move_selection(i);
+            }
+        }
+    }
+}
+
+// Unfolds the selected directory, or selects its first entry if it is
+// already unfolded.
+fn expand(state: &mut State) {
+    match state.lines.get(state.selected) {
+        Some(line) if line.folded > 0 => toggle_fold(state),
+        _ => {
+            if let Some(i) = state.first_child() {
+                state.move_selection(i);
+            }
+        }
+    }
+}
+
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+// Selects the next entry whose name starts with the characters typed in
+// quick succession. A single character moves on to the next such entry, so
+// repeating it cycles through them.
+fn type_ahead(state: &mut State, c: char) {
+    if state.type_ahead_at.elapsed() > TYPE_AHEAD_TIMEOUT {
+        state.type_ahead.clear();
+    }
+    state.type_ahead.push(c);
+    state.type_ahead_at = Instant::now();
+
+    let prefix = state.type_ahead.to_lowercase();
+    let start = if prefix.chars().count() == 1 {
+        state.selected + 1
+    } else {
+        state.selected
+    };
+
+    let count = state.lines.len();
+    let found = (0..count)
+        .map(|offset| (start + offset) % count)
+        .find(|&i| state.lines[i].name.to_lowercase().starts_with(&prefix));
+
+    if let Some(i) = found {
+        state.move_selection(i);
+    }
+}
+
+// Hides the selected entry for the rest of the session. The root can't be
+// hidden.
+fn hide_selected(state: &mut State) {
+    if state.selected == 0 {
+        return;
+    }
+
+    if let Some(path) = state.selected_path.clone() {
+        state.message = Some(trf("hid", &[&display_path(&path)]));
+        state.hidden.insert(path);
+    }
+}
+
+fn toggle_dotfiles(state: &mut State) {
+    state.show_hidden = !state.show_hidden;
+    state.message = Some(tr(if state.show_hidden {
+        "showing_dotfiles"
+    } else {
+        "hiding_dotfiles"
+    }));
+}
+
+// Leaves the TUI with the selected entry, or with the place of the selected
+// symbol.
+fn pick(state: &State) -> Option<Outcome> {
+    if !state.marked.is_empty() {
+        return Some(Outcome::Marked(state.marked.clone()));
+    }
+    match state
+        .lines
+        .get(state.selected)
+        .and_then(|line| line.tag.as_ref())
+    {
+        Some((path, tag)) => Some(Outcome::Symbol(path.clone(), tag.line(path))),
+        None => state.selected_path.clone().map(Outcome::Selected),
+    }
+}
+
+// Switches from names to contents, then to symbols when there is a tags
+// file, and back to names.
+fn toggle_search_target(state: &mut State, options: &DisplayOptions) {
+    let (content, symbols) = match (state.content_mode, state.symbol_mode) {
+        (false, false) => (true, false),
+        (true, _) if options.tags.is_some() => (false, true),
+        _ => (false, false),
+    };
+    state.content_mode = content;
+    state.symbol_mode = symbols;
+    state.message = Some(tr(if content {
+        "matching_contents"
+    } else if symbols {
+        "matching_symbols"
+    } else {
+        "matching_names"
+    }));
+}
+
+fn toggle_match_mode(state: &mut State) {
+    state.match_mode = match state.match_mode {
+        MatchMode::Regex => MatchMode::Fuzzy,
+        MatchMode::Fuzzy => MatchMode::Glob,
+        MatchMode::Glob => MatchMode::Regex,
+    };
+    state.message = Some(tr(match state.match_mode {
+        MatchMode::Regex => "regex_matching",
+        MatchMode::Fuzzy => "fuzzy_matching",
+        MatchMode::Glob => "glob_matching",
+    }));
+}
+
+fn toggle_case_conflicts(state: &mut State) {
+    state.only_case_conflicts = !state.only_case_conflicts;
+    state.message = Some(if state.only_case_conflicts {
+        trf("showing_case_conflicts", &[&state.case_conflicts.len()])
+    } else {
+        tr("showing_all")
+    });
+}
+
+fn unhide_all(state: &mut State) {
+    if !state.hidden.is_empty() {
+        state.message = Some(trf("unhid", &[&state.hidden.len()]));
+        state.hidden.clear();
+    }
+}
+
+fn trigger_plugin(state: &mut State, key: char) {
+    let plugin = match state.plugins.iter().find(|plugin| plugin.key == key) {
+        Some(plugin) => plugin,
+        None => return,
+    };
+
+    let selected: Vec<PathBuf> = state.selected_path.iter().cloned().collect();
+    let actions = run_plugin(
+        plugin,
+        &view_path(&state.dirname, &state.view_root),
+        &state.search_term,
+        &selected,
+        &mut state.annotations,
+    );
+
+    for action in actions {
+        match action {
+            PluginAction::Message(message) => state.message = Some(message),
+            PluginAction::Filter(pattern) => state.search_term = pattern,
+        }
+    }
+}
+
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+pub fn view_path(dirname: &Path, view_root: &[String]) -> PathBuf {
+    view_root
+        .iter()
+        .fold(dirname.to_path_buf(), |path, component| {
+            path.join(component)
+        })
+}
 
-    term_teardown(&mut terminal);
+// The project jump list may point at a directory that is no longer part of
+// the tree, in which case the whole tree is shown instead.
+pub fn view<'a>(root: &'a TreeNode, view_root: &[String]) -> &'a TreeNode {
+    subtree(root, view_root).unwrap_or(root)
 }
@@ -0,0 +1,395 @@
+// `--scaffold <file>` creates the layout a file describes under the root,
+// the other way round from exporting it. The description is either the
+// output of `--output json` or an outline with one name per line, indented
+// below its directory, such as a markdown list or the drawing `--print`
+// makes. In an outline, names ending in a slash and names with something
+// indented below them are directories.
+//
+// The TUI shows the layout first, with the entries that would be created
+// marked, and creates them on Enter. Without the TUI, or with --dry-run,
+// the changes are printed like a diff: + for what is created and ! for what
+// is in the way, such as a file where a directory should be. Entries that
+// already exist are left as they are.
+
+use crate::{
+    i18n::tr,
+    plugins::Annotation,
+    type_color,
+    util::{display_path, node_name},
+    NodeType, TreeNode,
+};
+use std::{
+    collections::HashMap,
+    io,
+    iter::Peekable,
+    path::{Component, Path, PathBuf},
+    str::Chars,
+};
+use tui::style::Color;
+
+pub struct Entry {
+    // Relative to the root.
+    pub path: PathBuf,
+    pub node_type: NodeType,
+}
+
+#[derive(PartialEq)]
+pub enum Status {
+    New,
+    Exists,
+    // Something of the other type is in the way.
+    Conflict,
+}
+
+pub struct Scaffold {
+    pub entries: Vec<Entry>,
+}
+
+impl Scaffold {
+    pub fn load(path: &Path) -> Result<Scaffold, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let entries = if text.trim_start().starts_with('{') {
+            let json = Json::parse(&text)?;
+            let mut entries = Vec::new();
+            json_entries(&json, Path::new(""), &mut entries)?;
+            entries
+        } else {
+            outline_entries(&text)
+        };
+        // Everything has to end up below the root.
+        let entries = entries
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .path
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+            })
+            .collect();
+        Ok(Scaffold { entries })
+    }
+
+    pub fn status(&self, entry: &Entry, root: &Path) -> Status {
+        match std::fs::symlink_metadata(root.join(&entry.path)) {
+            Err(_) => Status::New,
+            Ok(metadata) if metadata.is_dir() == (entry.node_type == NodeType::Dir) => {
+                Status::Exists
+            }
+            Ok(_) => Status::Conflict,
+        }
+    }
+
+    // The changes as a diff, one line per entry that is new or in the way.
+    pub fn diff(&self, root: &Path) -> String {
+        let mut diff = String::new();
+        for entry in &self.entries {
+            let mark = match self.status(entry, root) {
+                Status::New => '+',
+                Status::Conflict => '!',
+                Status::Exists => continue,
+            };
+            let slash = if entry.node_type == NodeType::Dir {
+                "/"
+            } else {
+                ""
+            };
+            diff.push_str(&format!(
+                "{} {}{}\n",
+                mark,
+                display_path(&entry.path),
+                slash
+            ));
+        }
+        diff
+    }
+
+    pub fn count(&self, root: &Path, status: Status) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| self.status(entry, root) == status)
+            .count()
+    }
+
+    // Creates the new entries, directories before what is in them. Returns
+    // how many were created.
+    pub fn apply(&self, root: &Path) -> io::Result<usize> {
+        let mut created = 0;
+        for entry in &self.entries {
+            if self.status(entry, root) != Status::New {
+                continue;
+            }
+            let path = root.join(&entry.path);
+            match entry.node_type {
+                NodeType::Dir => std::fs::create_dir_all(&path)?,
+                NodeType::File => {
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::File::create(&path)?;
+                }
+            }
+            created += 1;
+        }
+        Ok(created)
+    }
+
+    // Adds the described entries to the tree shown in the TUI.
+    pub fn add_to(&self, root: &mut TreeNode) {
+        for entry in &self.entries {
+            let mut node = &mut *root;
+            let count = entry.path.iter().count();
+            for (i, component) in entry.path.iter().enumerate() {
+                let val = component.to_string_lossy().to_string();
+                let index = match node.children.iter().position(|child| child.val == val) {
+                    Some(index) => index,
+                    None => {
+                        let node_type = if i + 1 == count {
+                            entry.node_type
+                        } else {
+                            NodeType::Dir
+                        };
+                        node.children.push(TreeNode {
+                            color: type_color(node_type),
+                            val,
+                            children: Vec::new(),
+                            node_type,
+                        });
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[index];
+            }
+        }
+    }
+
+    // Marks the entries that would be created or are in the way.
+    pub fn annotations(&self, root: &Path) -> HashMap<PathBuf, Annotation> {
+        let mut annotations = HashMap::new();
+        for entry in &self.entries {
+            let (text, color) = match self.status(entry, root) {
+                Status::New => (tr("scaffold_new"), Color::Green),
+                Status::Conflict => (tr("scaffold_conflict"), Color::Red),
+                Status::Exists => continue,
+            };
+            annotations.insert(
+                root.join(&entry.path),
+                Annotation {
+                    text: Some(text),
+                    color: Some(color),
+                },
+            );
+        }
+        annotations
+    }
+}
+
+// The entries below a node of `--output json`, named after the last
+// component of their paths.
+fn json_entries(node: &Json, path: &Path, entries: &mut Vec<Entry>) -> Result<(), String> {
+    let children = match node.get("children") {
+        Some(Json::Array(children)) => children,
+        _ => return Ok(()),
+    };
+    for child in children {
+        let name = match child.get("path") {
+            Some(Json::String(child_path)) => node_name(Path::new(child_path)),
+            _ => return Err("an entry without a path".to_string()),
+        };
+        let node_type = match child.get("type") {
+            Some(Json::String(node_type)) if node_type == "dir" => NodeType::Dir,
+            _ => NodeType::File,
+        };
+        let child_path = path.join(name);
+        entries.push(Entry {
+            path: child_path.clone(),
+            node_type,
+        });
+        json_entries(child, &child_path, entries)?;
+    }
+    Ok(())
+}
+
+// Characters an outline may draw before a name.
+const DRAWING: [char; 6] = [' ', '\t', '│', '├', '└', '─'];
+
+fn outline_entries(text: &str) -> Vec<Entry> {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with("```"))
+        .collect();
+    // A drawing starts with the root, which the entries go under instead.
+    let drawn = lines.iter().any(|line| line.contains(['├', '└']));
+    let skip = usize::from(drawn);
+
+    let items: Vec<(usize, &str)> = lines
+        .iter()
+        .skip(skip)
+        .map(|line| {
+            let name = line.trim_start_matches(DRAWING);
+            let indent = line.chars().count() - name.chars().count();
+            let name = ["- ", "* ", "+ "]
+                .iter()
+                .find_map(|marker| name.strip_prefix(marker))
+                .unwrap_or(name);
+            (indent, name.trim_end())
+        })
+        .collect();
+
+    // The directories leading to the current line, with their indentation.
+    let mut parents: Vec<(usize, PathBuf)> = Vec::new();
+    let mut entries = Vec::new();
+    for (i, &(indent, name)) in items.iter().enumerate() {
+        while parents.last().is_some_and(|(depth, _)| *depth >= indent) {
+            parents.pop();
+        }
+        let path = parents
+            .last()
+            .map_or(PathBuf::new(), |(_, path)| path.clone())
+            .join(name.trim_end_matches('/'));
+        let nested = items.get(i + 1).is_some_and(|(next, _)| *next > indent);
+        let node_type = if name.ends_with('/') || nested {
+            NodeType::Dir
+        } else {
+            NodeType::File
+        };
+        if node_type == NodeType::Dir {
+            parents.push((indent, path.clone()));
+        }
+        entries.push(Entry { path, node_type });
+    }
+    entries
+}
+
+// Just enough JSON to read back what `--output json` writes.
+enum Json {
+    Null,
+    Bool,
+    Number,
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected {} after the end", c)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars>, word: &str) -> Result<(), String> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return Err(format!("expected {}", word));
+        }
+    }
+    Ok(())
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                match chars.peek() {
+                    Some('}') if fields.is_empty() => {
+                        chars.next();
+                        break;
+                    }
+                    Some('"') => {}
+                    _ => return Err("expected a key".to_string()),
+                }
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ":")?;
+                fields.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some('}') => break,
+                    _ => return Err("expected , or }".to_string()),
+                }
+            }
+            Ok(Json::Object(fields))
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_some() {
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => break,
+                    _ => return Err("expected , or ]".to_string()),
+                }
+            }
+            Ok(Json::Array(items))
+        }
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('t') => expect(chars, "true").map(|_| Json::Bool),
+        Some('f') => expect(chars, "false").map(|_| Json::Bool),
+        Some('n') => expect(chars, "null").map(|_| Json::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            while chars
+                .next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c))
+                .is_some()
+            {}
+            Ok(Json::Number)
+        }
+        Some(c) => Err(format!("unexpected {}", c)),
+        None => Err("unexpected end".to_string()),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, "\"")?;
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('r') => string.push('\r'),
+                Some('t') => string.push('\t'),
+                Some('b') => string.push('\u{8}'),
+                Some('f') => string.push('\u{c}'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                    string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c) => string.push(c),
+                None => return Err("unexpected end".to_string()),
+            },
+            Some(c) => string.push(c),
+            None => return Err("unexpected end".to_string()),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// Screenshots of the last drawn frame, for documentation and bug reports.
+// The frame is written cell by cell as text with ANSI colors, as HTML or as
+// SVG, depending on the extension of the file it is saved to.
+
+use std::{fmt::Write, path::Path};
+use tui::{
+    buffer::{Buffer, Cell},
+    style::{Color, Modifier},
+};
+
+// Cell size in SVG user units, for a monospace font at 14px.
+const CELL_WIDTH: f32 = 8.4;
+const CELL_HEIGHT: f32 = 18.0;
+
+const FOREGROUND: &str = "#d0d0d0";
+const BACKGROUND: &str = "#1c1c1c";
+
+pub fn save(buffer: &Buffer, path: &Path) -> std::io::Result<()> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let contents = match extension.as_deref() {
+        Some("svg") => svg(buffer),
+        Some("html") | Some("htm") => html(buffer),
+        _ => ansi(buffer),
+    };
+    std::fs::write(path, contents)
+}
+
+fn rows(buffer: &Buffer) -> impl Iterator<Item = Vec<&Cell>> {
+    let area = buffer.area;
+    (area.top()..area.bottom()).map(move |y| {
+        (area.left()..area.right())
+            .map(|x| buffer.get(x, y))
+            .collect()
+    })
+}
+
+pub fn ansi(buffer: &Buffer) -> String {
+    let mut text = String::new();
+    for row in rows(buffer) {
+        let mut previous = None;
+        for cell in row {
+            let style = (cell.fg, cell.bg, cell.modifier);
+            if previous != Some(style) {
+                text.push_str(&sgr(cell));
+                previous = Some(style);
+            }
+            text.push_str(&cell.symbol);
+        }
+        text.push_str("\x1b[0m\n");
+    }
+    text
+}
+
+fn sgr(cell: &Cell) -> String {
+    let mut codes = vec!["0".to_string()];
+    for (modifier, code) in [
+        (Modifier::BOLD, "1"),
+        (Modifier::DIM, "2"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::REVERSED, "7"),
+    ] {
+        if cell.modifier.contains(modifier) {
+            codes.push(code.to_string());
+        }
+    }
+    if let Some(code) = sgr_color(cell.fg, 30) {
+        codes.push(code);
+    }
+    if let Some(code) = sgr_color(cell.bg, 40) {
+        codes.push(code);
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+// The SGR parameter of a color, where base is 30 for the foreground and 40
+// for the background.
+fn sgr_color(color: Color, base: u8) -> Option<String> {
+    let code = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::Indexed(i) => return Some(format!("{};5;{}", base + 8, i)),
+        Color::Black => base,
+        Color::Red => base + 1,
+        Color::Green => base + 2,
+        Color::Yellow => base + 3,
+        Color::Blue => base + 4,
+        Color::Magenta => base + 5,
+        Color::Cyan => base + 6,
+        Color::Gray => base + 7,
+        Color::DarkGray => base + 60,
+        Color::LightRed => base + 61,
+        Color::LightGreen => base + 62,
+        Color::LightYellow => base + 63,
+        Color::LightBlue => base + 64,
+        Color::LightMagenta => base + 65,
+        Color::LightCyan => base + 66,
+        Color::White => base + 67,
+    };
+    Some(code.to_string())
+}
+
+// The xterm default of a color as #rrggbb, or None for the default color.
+fn hex(color: Color) -> Option<String> {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+
+    let (r, g, b) = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(i) if i < 16 => BASIC[i as usize],
+        // The 6x6x6 color cube followed by a ramp of grays.
+        Color::Indexed(i) if i < 232 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        Color::Indexed(i) => {
+            let gray = 8 + (i - 232) * 10;
+            (gray, gray, gray)
+        }
+        Color::Black => BASIC[0],
+        Color::Red => BASIC[1],
+        Color::Green => BASIC[2],
+        Color::Yellow => BASIC[3],
+        Color::Blue => BASIC[4],
+        Color::Magenta => BASIC[5],
+        Color::Cyan => BASIC[6],
+        Color::Gray => BASIC[7],
+        Color::DarkGray => BASIC[8],
+        Color::LightRed => BASIC[9],
+        Color::LightGreen => BASIC[10],
+        Color::LightYellow => BASIC[11],
+        Color::LightBlue => BASIC[12],
+        Color::LightMagenta => BASIC[13],
+        Color::LightCyan => BASIC[14],
+        Color::White => BASIC[15],
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+// The colors a cell is painted with, with reversal applied.
+fn colors(cell: &Cell) -> (String, String) {
+    let fg = hex(cell.fg).unwrap_or_else(|| FOREGROUND.to_string());
+    let bg = hex(cell.bg).unwrap_or_else(|| BACKGROUND.to_string());
+    if cell.modifier.contains(Modifier::REVERSED) {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn html(buffer: &Buffer) -> String {
+    let mut html = format!(
+        "<pre style=\"color: {}; background: {}; font-family: monospace; line-height: 1.2\">",
+        FOREGROUND, BACKGROUND
+    );
+    for row in rows(buffer) {
+        // Runs of cells in the same style share a span.
+        for run in row.chunk_by(|a, b| (a.fg, a.bg, a.modifier) == (b.fg, b.bg, b.modifier)) {
+            let (fg, bg) = colors(run[0]);
+            let mut style = format!("color: {}; background: {}", fg, bg);
+            if run[0].modifier.contains(Modifier::BOLD) {
+                style.push_str("; font-weight: bold");
+            }
+            if run[0].modifier.contains(Modifier::ITALIC) {
+                style.push_str("; font-style: italic");
+            }
+            if run[0].modifier.contains(Modifier::UNDERLINED) {
+                style.push_str("; text-decoration: underline");
+            }
+            let text: String = run.iter().map(|cell| cell.symbol.as_str()).collect();
+            let _ = write!(html, "<span style=\"{}\">{}</span>", style, escape(&text));
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n");
+    html
+}
+
+pub fn svg(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let width = area.width as f32 * CELL_WIDTH;
+    let height = area.height as f32 * CELL_HEIGHT;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"monospace\" font-size=\"14\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+        width, height, BACKGROUND
+    );
+    for (y, row) in rows(buffer).enumerate() {
+        let top = y as f32 * CELL_HEIGHT;
+        for (x, cell) in row.into_iter().enumerate() {
+            let left = x as f32 * CELL_WIDTH;
+            let (fg, bg) = colors(cell);
+            if bg != BACKGROUND {
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                    left, top, CELL_WIDTH, CELL_HEIGHT, bg
+                );
+            }
+            if cell.symbol.trim().is_empty() {
+                continue;
+            }
+            let weight = if cell.modifier.contains(Modifier::BOLD) {
+                " font-weight=\"bold\""
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" fill=\"{}\"{}>{}</text>",
+                left,
+                top + CELL_HEIGHT * 0.75,
+                fg,
+                weight,
+                escape(&cell.symbol)
+            );
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,85 @@
+// User scripts are written in Rhai and loaded from `--script` or
+// `~/.config/tree-rs/init.rhai`. A script may define any of:
+//
+//     fn column(path, name, is_dir)  returns text for an extra column
+//     fn filter(path, name, is_dir)  returns false to hide the entry
+//     fn on_select(path)             returns text to show in the status bar
+//
+// Functions that aren't defined are skipped.
+
+use crate::{
+    util::{config_dir, display_path},
+    NodeType,
+};
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::path::{Path, PathBuf};
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+pub fn default_script_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("init.rhai"))
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Script, String> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Script { engine, ast })
+    }
+
+    pub fn has_filter(&self) -> bool {
+        self.defines("filter")
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name)
+    }
+
+    fn call(&self, name: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, String> {
+        self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, args)
+            .map_err(|e| format!("{}: {}", name, e))
+    }
+
+    pub fn column(&self, path: &Path, name: &str, node_type: NodeType) -> Option<String> {
+        if !self.defines("column") {
+            return None;
+        }
+
+        let args = (path_arg(path), name.to_string(), node_type == NodeType::Dir);
+        match self.call("column", args) {
+            Ok(value) if value.is_unit() => None,
+            Ok(value) => Some(value.to_string()),
+            Err(e) => Some(e),
+        }
+    }
+
+    pub fn keep(&self, path: &Path, name: &str, node_type: NodeType) -> bool {
+        let args = (path_arg(path), name.to_string(), node_type == NodeType::Dir);
+        match self.call("filter", args) {
+            Ok(value) => value.as_bool().unwrap_or(true),
+            Err(_) => true,
+        }
+    }
+
+    pub fn on_select(&self, path: &Path) -> Option<String> {
+        if !self.defines("on_select") {
+            return None;
+        }
+
+        match self.call("on_select", (path_arg(path),)) {
+            Ok(value) if value.is_unit() => None,
+            Ok(value) => Some(value.to_string()),
+            Err(e) => Some(e),
+        }
+    }
+}
+
+fn path_arg(path: &Path) -> String {
+    display_path(path)
+}
@@ -0,0 +1,125 @@
+// A snapshot records the size of every directory at one point in time, so
+// the size columns can show how much each one grew or shrank since, e.g.
+// "1.2G ▲ +120M". `--snapshot <file>` compares against the file when it
+// exists, and `--update-snapshot` writes the current sizes to it instead of
+// starting the TUI, e.g. from a nightly cron job.
+//
+// The file has one line per directory: its apparent size and disk usage in
+// bytes and its path relative to the root, separated by tabs. Paths are
+// relative so the root can be given differently next time.
+
+use crate::annotate::{disk_usage, human_size};
+use crate::util::display_path;
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const HEADER: &str = "# apparent size, disk usage and path of each directory";
+
+pub struct Snapshot {
+    root: PathBuf,
+    sizes: HashMap<PathBuf, (u64, u64)>,
+}
+
+// The snapshot given with --snapshot, if it exists.
+static SNAPSHOT: OnceLock<Snapshot> = OnceLock::new();
+
+pub fn set_snapshot(snapshot: Snapshot) {
+    let _ = SNAPSHOT.set(snapshot);
+}
+
+pub fn snapshot() -> Option<&'static Snapshot> {
+    SNAPSHOT.get()
+}
+
+impl Snapshot {
+    // A missing file is an empty snapshot, as there is nothing to compare
+    // with before the first update. Lines that can't be read are skipped.
+    pub fn load(path: &Path, root: &Path) -> io::Result<Snapshot> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let sizes = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let apparent = fields.next()?.parse().ok()?;
+                let disk = fields.next()?.parse().ok()?;
+                Some((PathBuf::from(fields.next()?), (apparent, disk)))
+            })
+            .collect();
+        Ok(Snapshot {
+            root: root.to_path_buf(),
+            sizes,
+        })
+    }
+
+    // Measures every directory below the root.
+    pub fn take(root: &Path) -> Snapshot {
+        let mut sizes = HashMap::new();
+        measure(root, Path::new(""), &mut sizes);
+        Snapshot {
+            root: root.to_path_buf(),
+            sizes,
+        }
+    }
+
+    // How many directories were measured.
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut dirs: Vec<_> = self.sizes.iter().collect();
+        dirs.sort();
+        let mut text = format!("{}\n", HEADER);
+        for (dir, (apparent, disk)) in dirs {
+            text.push_str(&format!("{}\t{}\t{}\n", apparent, disk, display_path(dir)));
+        }
+        std::fs::write(path, text)
+    }
+
+    // How a directory's size changed since the snapshot, e.g. "▲ +120M", or
+    // None for directories that are new or didn't change.
+    pub fn delta(&self, path: &Path, size: u64, disk: bool) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let (apparent, usage) = self.sizes.get(relative)?;
+        let before = if disk { *usage } else { *apparent };
+        match size.cmp(&before) {
+            std::cmp::Ordering::Greater => Some(format!("▲ +{}", human_size(size - before))),
+            std::cmp::Ordering::Less => Some(format!("▼ -{}", human_size(before - size))),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+// The sizes of a directory and of everything in it, without following
+// symlinks. Directories are recorded on the way.
+fn measure(path: &Path, relative: &Path, sizes: &mut HashMap<PathBuf, (u64, u64)>) -> (u64, u64) {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return (0, 0),
+    };
+    let mut apparent = metadata.len();
+    let mut disk = disk_usage(&metadata);
+
+    if metadata.is_dir() {
+        if let Ok(entries) = std::fs::read_dir(path) {
+            for entry in entries.flatten() {
+                let (child_apparent, child_disk) =
+                    measure(&entry.path(), &relative.join(entry.file_name()), sizes);
+                apparent += child_apparent;
+                disk += child_disk;
+            }
+        }
+        sizes.insert(relative.to_path_buf(), (apparent, disk));
+    }
+
+    (apparent, disk)
+}
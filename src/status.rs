@@ -0,0 +1,93 @@
+use crate::{
+    i18n::{tr, trf},
+    palette::Palette,
+};
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+};
+
+pub const DEFAULT_STATUS_FORMAT: &str = "{pattern}";
+
+pub struct StatusInfo<'a> {
+    pub pattern: &'a str,
+    pub matches: usize,
+    pub files: usize,
+    pub dirs: usize,
+    pub scanning: bool,
+    // The root as the tree shows it, the path or --root-label.
+    pub root: &'a str,
+    // The selected entry and its size, when something is selected.
+    pub path: Option<&'a str>,
+    pub size: Option<String>,
+    pub error_position: Option<usize>,
+}
+
+// Expands the placeholders in a status bar format string. Unknown
+// placeholders are left untouched so typos are visible in the bar.
+pub fn format_status(format: &str, info: &StatusInfo, palette: &Palette) -> Spans<'static> {
+    let mut spans = Vec::new();
+    let mut status = String::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find('{') {
+        status.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+
+        match &rest[1..end] {
+            "pattern" => match info.error_position {
+                Some(position) => {
+                    spans.push(Span::raw(std::mem::take(&mut status)));
+                    spans.extend(underline_at(info.pattern, position, palette));
+                }
+                None => status.push_str(info.pattern),
+            },
+            "matches" => status.push_str(&info.matches.to_string()),
+            "files" => status.push_str(&info.files.to_string()),
+            "dirs" => status.push_str(&info.dirs.to_string()),
+            "scan" if info.scanning => {
+                status.push_str(&trf("scanning", &[&(info.files + info.dirs)]))
+            }
+            "scan" => status.push_str(&tr("done")),
+            "root" => status.push_str(info.root),
+            "path" => status.push_str(info.path.unwrap_or_default()),
+            "size" => status.push_str(info.size.as_deref().unwrap_or_default()),
+            "hints" => status.push_str(&tr("hints")),
+            _ => status.push_str(&rest[..=end]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    status.push_str(rest);
+    spans.push(Span::raw(status));
+    Spans::from(spans)
+}
+
+fn underline_at(pattern: &str, position: usize, palette: &Palette) -> Vec<Span<'static>> {
+    let style = Style::default()
+        .fg(palette.error)
+        .add_modifier(Modifier::UNDERLINED | Modifier::BOLD);
+
+    let before: String = pattern.chars().take(position).collect();
+    let offending: String = pattern.chars().skip(position).take(1).collect();
+    let after: String = pattern.chars().skip(position + 1).collect();
+
+    // Errors such as an unclosed group point past the end of the pattern.
+    let offending = if offending.is_empty() {
+        " ".to_string()
+    } else {
+        offending
+    };
+
+    vec![
+        Span::raw(before),
+        Span::styled(offending, style),
+        Span::raw(after),
+    ]
+}
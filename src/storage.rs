@@ -0,0 +1,208 @@
+// Scanning is tuned to the storage the tree is on. Solid state drives cope
+// well with many concurrent reads, while on spinning disks they cause seeks
+// and on network mounts each one is a round trip, so those get fewer
+// annotation workers and smaller scan chunks. The storage is detected from
+// the mount table on Linux and can be set with --storage.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Storage {
+    Ssd,
+    Hdd,
+    Network,
+    Unknown,
+}
+
+pub const STORAGE_NAMES: [&str; 4] = ["auto", "ssd", "hdd", "network"];
+
+pub struct Tuning {
+    // Threads computing columns in the background.
+    pub workers: usize,
+    // Entries read between two redraws while scanning.
+    pub chunk: i32,
+}
+
+impl Storage {
+    pub fn from_name(name: &str, path: &Path) -> Storage {
+        match name {
+            "ssd" => Storage::Ssd,
+            "hdd" => Storage::Hdd,
+            "network" => Storage::Network,
+            _ => detect(path),
+        }
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        match self {
+            Storage::Ssd => Tuning {
+                workers: 8,
+                chunk: 500,
+            },
+            Storage::Hdd => Tuning {
+                workers: 1,
+                chunk: 100,
+            },
+            Storage::Network => Tuning {
+                workers: 2,
+                chunk: 50,
+            },
+            Storage::Unknown => Tuning {
+                workers: 4,
+                chunk: 100,
+            },
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+const NETWORK_FILESYSTEMS: [&str; 8] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "fuse.sshfs",
+    "afs",
+];
+
+#[cfg(target_os = "linux")]
+pub fn detect(path: &Path) -> Storage {
+    let mountinfo = match std::fs::read_to_string("/proc/self/mountinfo") {
+        Ok(mountinfo) => mountinfo,
+        Err(_) => return Storage::Unknown,
+    };
+
+    // Each line reads "id parent major:minor root mount-point options
+    // [optional fields] - type source super-options". The mount the path
+    // is on is the one with the longest matching mount point.
+    let mut best: Option<(usize, &str, &str)> = None;
+    for line in mountinfo.lines() {
+        let (mount, filesystem) = match line.split_once(" - ") {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let fields: Vec<&str> = mount.split(' ').collect();
+        let (device, mount_point) = match (fields.get(2), fields.get(4)) {
+            (Some(device), Some(mount_point)) => (*device, *mount_point),
+            _ => continue,
+        };
+        let filesystem = filesystem.split(' ').next().unwrap_or("");
+
+        if path.starts_with(mount_point) && best.is_none_or(|(len, _, _)| mount_point.len() >= len)
+        {
+            best = Some((mount_point.len(), device, filesystem));
+        }
+    }
+
+    let (_, device, filesystem) = match best {
+        Some(best) => best,
+        None => return Storage::Unknown,
+    };
+
+    if NETWORK_FILESYSTEMS.contains(&filesystem) {
+        return Storage::Network;
+    }
+
+    // Partitions don't have a queue of their own, their disk does.
+    let block = Path::new("/sys/dev/block").join(device);
+    let rotational = std::fs::read_to_string(block.join("queue/rotational"))
+        .or_else(|_| std::fs::read_to_string(block.join("../queue/rotational")));
+    match rotational.as_deref().map(str::trim) {
+        Ok("1") => Storage::Hdd,
+        Ok("0") => Storage::Ssd,
+        _ => Storage::Unknown,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn detect(_path: &Path) -> Storage {
+    Storage::Unknown
+}
+
+// Filesystems the kernel makes up on the fly. Their entries aren't files on
+// any storage, some can't be read or block when read, and /proc alone lists
+// thousands of them, so scans don't descend into them by default.
+#[cfg(target_os = "linux")]
+const VIRTUAL_FILESYSTEMS: [&str; 12] = [
+    "proc",
+    "sysfs",
+    "devtmpfs",
+    "devpts",
+    "cgroup",
+    "cgroup2",
+    "securityfs",
+    "debugfs",
+    "tracefs",
+    "pstore",
+    "bpf",
+    "configfs",
+];
+
+// The mount points of virtual filesystems.
+#[cfg(target_os = "linux")]
+pub fn virtual_mounts() -> Vec<PathBuf> {
+    mounts_of(&VIRTUAL_FILESYSTEMS)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn virtual_mounts() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+// The mount points of network filesystems, whose directories are read with
+// a timeout.
+#[cfg(target_os = "linux")]
+pub fn network_mounts() -> Vec<PathBuf> {
+    mounts_of(&NETWORK_FILESYSTEMS)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn network_mounts() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+fn mounts_of(filesystems: &[&str]) -> Vec<PathBuf> {
+    let mountinfo = match std::fs::read_to_string("/proc/self/mountinfo") {
+        Ok(mountinfo) => mountinfo,
+        Err(_) => return Vec::new(),
+    };
+
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            let (mount, filesystem) = line.split_once(" - ")?;
+            let mount_point = mount.split(' ').nth(4)?;
+            let filesystem = filesystem.split(' ').next()?;
+            filesystems
+                .contains(&filesystem)
+                .then(|| PathBuf::from(unescape(mount_point)))
+        })
+        .collect()
+}
+
+// Mount points in the mount table have spaces and a few other characters
+// written as octal escapes, e.g. \040 for a space.
+#[cfg(target_os = "linux")]
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('\\') {
+        unescaped.push_str(&rest[..start]);
+        let code = rest.get(start + 1..start + 4);
+        match code.and_then(|code| u8::from_str_radix(code, 8).ok()) {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[start + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
@@ -0,0 +1,156 @@
+// With --tags the symbols in a tags file, as written by ctags, can be
+// searched instead of names: Ctrl+L switches to symbols after contents. Files
+// with a matching symbol are listed with the symbols under them, and Enter
+// on a symbol opens the editor at its line.
+//
+// Each line of a tags file is `name<TAB>file<TAB>address` followed by `;"`
+// and extra fields such as the kind and `line:42`. The address is a line
+// number or a search pattern like `/^fn main() {$/`, which is looked up in
+// the file when the symbol is opened. Files are relative to the directory
+// the tags file is in.
+
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone)]
+pub enum Address {
+    Line(usize),
+    Pattern(String),
+}
+
+#[derive(Clone)]
+pub struct Tag {
+    pub name: String,
+    pub kind: Option<String>,
+    pub address: Address,
+}
+
+impl Tag {
+    pub fn display(&self) -> String {
+        match &self.kind {
+            Some(kind) => format!("{} ({})", self.name, kind),
+            None => self.name.clone(),
+        }
+    }
+
+    // The line of the symbol in a file, counted from 1.
+    pub fn line(&self, path: &Path) -> Option<usize> {
+        let pattern = match &self.address {
+            Address::Line(line) => return Some(*line),
+            Address::Pattern(pattern) => pattern,
+        };
+        let text = std::fs::read_to_string(path).ok()?;
+        let anchored_start = pattern.starts_with('^');
+        let anchored_end = pattern.ends_with('$') && !pattern.ends_with("\\$");
+        let mut wanted = pattern.as_str();
+        if anchored_start {
+            wanted = &wanted[1..];
+        }
+        if anchored_end {
+            wanted = &wanted[..wanted.len() - 1];
+        }
+        let wanted = wanted.replace("\\/", "/").replace("\\\\", "\\");
+
+        text.lines()
+            .position(|line| match (anchored_start, anchored_end) {
+                (true, true) => line == wanted,
+                (true, false) => line.starts_with(&wanted),
+                (false, true) => line.ends_with(&wanted),
+                (false, false) => line.contains(&wanted),
+            })
+            .map(|i| i + 1)
+    }
+}
+
+pub struct Tags {
+    by_file: HashMap<PathBuf, Vec<Tag>>,
+}
+
+impl Tags {
+    pub fn load(path: &Path) -> io::Result<Tags> {
+        let text = std::fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+
+        let mut by_file: HashMap<PathBuf, Vec<Tag>> = HashMap::new();
+        for line in text.lines().filter(|line| !line.starts_with("!_TAG_")) {
+            if let Some((file, tag)) = parse_line(line) {
+                by_file.entry(dir.join(file)).or_default().push(tag);
+            }
+        }
+        Ok(Tags { by_file })
+    }
+
+    // The symbols of a file whose names the pattern matches.
+    pub fn matching(&self, path: &Path, filter: &Regex) -> Vec<&Tag> {
+        self.by_file
+            .get(path)
+            .map(|tags| {
+                tags.iter()
+                    .filter(|tag| filter.is_match(&tag.name))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn count(&self, filter: &Regex) -> usize {
+        self.by_file
+            .values()
+            .flatten()
+            .filter(|tag| filter.is_match(&tag.name))
+            .count()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(&str, Tag)> {
+    let mut fields = line.splitn(3, '\t');
+    let name = fields.next()?;
+    let file = fields.next()?;
+    let rest = fields.next()?;
+
+    // The address ends at `;"`, after which come the extra fields. Old
+    // tags files have no extra fields.
+    let (address, extra) = rest
+        .split_once(";\"\t")
+        .or_else(|| rest.strip_suffix(";\"").map(|address| (address, "")))
+        .unwrap_or((rest, ""));
+    let mut kind = None;
+    let mut number = None;
+    for field in extra.split('\t').filter(|field| !field.is_empty()) {
+        match field.split_once(':') {
+            Some(("kind", value)) => kind = Some(value.to_string()),
+            Some(("line", value)) => number = value.parse().ok(),
+            Some(_) => {}
+            // A field without a name is the kind, usually a single letter.
+            None => kind = Some(field.to_string()),
+        }
+    }
+
+    let address = match number.or_else(|| address.parse().ok()) {
+        Some(number) => Address::Line(number),
+        None => {
+            let pattern = address
+                .strip_prefix('/')
+                .and_then(|address| address.strip_suffix('/'))
+                .or_else(|| {
+                    address
+                        .strip_prefix('?')
+                        .and_then(|address| address.strip_suffix('?'))
+                })?;
+            Address::Pattern(pattern.to_string())
+        }
+    };
+
+    Some((
+        file,
+        Tag {
+            name: name.to_string(),
+            kind,
+            address,
+        },
+    ))
+}
@@ -0,0 +1,89 @@
+// Batched statx calls through io_uring. io_uring has no operation for
+// reading directories, so listing them is still left to getdents.
+
+use io_uring::{opcode, types, IoUring};
+use std::{cell::RefCell, ffi::CString, os::unix::ffi::OsStrExt, path::PathBuf};
+
+const QUEUE_DEPTH: u32 = 64;
+
+thread_local! {
+    static RING: RefCell<Option<IoUring>> = RefCell::new(IoUring::new(QUEUE_DEPTH).ok());
+}
+
+pub fn available() -> bool {
+    RING.with(|ring| ring.borrow().is_some())
+}
+
+// Whether each path is a directory, following symlinks. Paths that can't be
+// stat'ed are not. Returns None if the ring fails, which then isn't used
+// again: requests may still be running in it, and their completions would
+// be taken for those of the next call.
+pub fn are_dirs(paths: &[PathBuf]) -> Option<Vec<bool>> {
+    RING.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let ring = slot.as_mut()?;
+
+        let mut dirs = Vec::with_capacity(paths.len());
+        for chunk in paths.chunks(QUEUE_DEPTH as usize) {
+            match stat_chunk(ring, chunk) {
+                Some(results) => dirs.extend(results),
+                None => {
+                    *slot = None;
+                    return None;
+                }
+            }
+        }
+        Some(dirs)
+    })
+}
+
+fn stat_chunk(ring: &mut IoUring, chunk: &[PathBuf]) -> Option<Vec<bool>> {
+    let names: Vec<CString> = chunk
+        .iter()
+        .map(|path| CString::new(path.as_os_str().as_bytes()).unwrap_or_default())
+        .collect();
+    // SAFETY: statx is plain data for which all zeroes is valid.
+    let mut buffers: Vec<libc::statx> = vec![unsafe { std::mem::zeroed() }; chunk.len()];
+
+    // The kernel may still write into the buffers and read the names after
+    // a failure, so they are leaked rather than freed then.
+    let abandon = |names: Vec<CString>, buffers: Vec<libc::statx>| {
+        std::mem::forget(names);
+        std::mem::forget(buffers);
+        None
+    };
+
+    for (i, (name, buffer)) in names.iter().zip(buffers.iter_mut()).enumerate() {
+        let entry = opcode::Statx::new(
+            types::Fd(libc::AT_FDCWD),
+            name.as_ptr(),
+            buffer as *mut libc::statx as *mut types::statx,
+        )
+        .mask(libc::STATX_TYPE)
+        .build()
+        .user_data(i as u64);
+
+        // SAFETY: the name and buffer outlive the operation, since all of
+        // them are either waited for below or leaked.
+        if unsafe { ring.submission().push(&entry) }.is_err() {
+            return abandon(names, buffers);
+        }
+    }
+
+    let mut results = vec![false; chunk.len()];
+    let mut completed = 0;
+    while completed < chunk.len() {
+        match ring.submit_and_wait(chunk.len() - completed) {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => return abandon(names, buffers),
+            Ok(_) => {}
+        }
+        for completion in ring.completion() {
+            let i = completion.user_data() as usize;
+            let mode = buffers[i].stx_mode as u32;
+            results[i] = completion.result() >= 0 && mode & libc::S_IFMT == libc::S_IFDIR;
+            completed += 1;
+        }
+    }
+    Some(results)
+}
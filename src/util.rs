@@ -1,14 +1,60 @@
 use crate::{NodeType, TreeNode};
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+    },
 };
-use std::{io, path::PathBuf};
-use tui::{backend::CrosstermBackend, Terminal};
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+};
+use tui::{backend::CrosstermBackend, style::Color, Terminal};
+
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+// FNV-1a, a hash that stays the same across builds and versions. It starts
+// from FNV_OFFSET, or from the hash of what came before, so long input can
+// be hashed a chunk at a time.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// The name shown for an entry. Roots such as / or C:\ have no file name, so
+// the whole path is used for them.
+pub fn node_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => display_path(path),
+    }
+}
+
+// On Windows canonical paths carry the verbatim prefix \\?\, which lifts
+// the length limit on paths but isn't something users type. It is dropped
+// for display, so \\?\C:\dir is shown as C:\dir and \\?\UNC\server\share
+// as \\server\share.
+pub fn display_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if let Some(share) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", share)
+    } else if let Some(path) = path.strip_prefix(r"\\?\") {
+        path.to_string()
+    } else {
+        path.to_string()
+    }
+}
 
 pub fn print_node_name(dirname: &PathBuf) {
-    match get_filetype(&dirname) {
+    match get_filetype(dirname) {
         0 => {
             print!("\x1b[{}m", 31);
             println!("{}", dirname.file_name().unwrap().to_str().unwrap());
@@ -62,7 +108,196 @@ pub fn sort_tree(root: &mut TreeNode) {
     }
 }
 
-pub fn filter_tree(root: &TreeNode, filter: &str) -> TreeNode {
+// Turns the order of siblings around, at every level.
+pub fn reverse_tree(root: &mut TreeNode) {
+    root.children.reverse();
+
+    for child in &mut root.children {
+        reverse_tree(child);
+    }
+}
+
+// Orders siblings by descending key. Entries without a key yet keep their
+// relative order after the ones that have one.
+pub fn sort_tree_by_key(
+    root: &mut TreeNode,
+    path: &Path,
+    key: &dyn Fn(&Path, &TreeNode) -> Option<u64>,
+) {
+    root.children
+        .sort_by_cached_key(|child| std::cmp::Reverse(key(&path.join(&child.val), child)));
+
+    for child in &mut root.children {
+        let child_path = path.join(&child.val);
+        sort_tree_by_key(child, &child_path, key);
+    }
+}
+
+// Orders siblings by ascending key. Siblings with equal keys keep their
+// relative order.
+pub fn sort_tree_by<K: Ord>(root: &mut TreeNode, path: &Path, key: &dyn Fn(&Path, &TreeNode) -> K) {
+    root.children
+        .sort_by_cached_key(|child| key(&path.join(&child.val), child));
+
+    for child in &mut root.children {
+        let child_path = path.join(&child.val);
+        sort_tree_by(child, &child_path, key);
+    }
+}
+
+// Orders siblings by the best score of anything in them, the best first.
+// Entries without a score keep their relative order after the others.
+// Returns the best score in the tree below the root.
+pub fn sort_tree_by_score<K: Ord + Copy>(
+    root: &mut TreeNode,
+    path: &Path,
+    score: &dyn Fn(&Path, &str) -> Option<K>,
+) -> Option<K> {
+    let children = std::mem::take(&mut root.children);
+    let mut scored: Vec<(Option<K>, TreeNode)> = children
+        .into_iter()
+        .map(|mut child| {
+            let child_path = path.join(&child.val);
+            let best = sort_tree_by_score(&mut child, &child_path, score)
+                .max(score(&child_path, &child.val));
+            (best, child)
+        })
+        .collect();
+    scored.sort_by_key(|(best, _)| std::cmp::Reverse(*best));
+
+    let best = scored.first().and_then(|(best, _)| *best);
+    root.children = scored.into_iter().map(|(_, child)| child).collect();
+    best
+}
+
+// Returns a copy of the tree where directories containing nothing but one
+// directory are joined with it, so a chain like src/main/java/org is shown
+// as one entry, named with the whole chain, that stands for the last one.
+pub fn compact_tree(root: &TreeNode) -> TreeNode {
+    TreeNode {
+        color: root.color,
+        val: root.val.clone(),
+        children: root.children.iter().map(compact_chain).collect(),
+        node_type: root.node_type,
+    }
+}
+
+fn compact_chain(node: &TreeNode) -> TreeNode {
+    let mut last = node;
+    let mut val = node.val.clone();
+    while last.node_type == NodeType::Dir
+        && last.children.len() == 1
+        && last.children[0].node_type == NodeType::Dir
+    {
+        last = &last.children[0];
+        val = format!("{}{}{}", val, std::path::MAIN_SEPARATOR, last.val);
+    }
+
+    let mut compacted = compact_tree(last);
+    compacted.val = val;
+    compacted
+}
+
+// Returns a copy of the tree without the entries rejected by `keep`.
+// Rejected directories are dropped with their contents.
+pub fn prune_tree(
+    root: &TreeNode,
+    path: &Path,
+    keep: &dyn Fn(&Path, &TreeNode) -> bool,
+) -> TreeNode {
+    let mut new_root = TreeNode {
+        color: root.color,
+        val: root.val.clone(),
+        children: Vec::new(),
+        node_type: root.node_type,
+    };
+
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        if keep(&child_path, child) {
+            new_root.children.push(prune_tree(child, &child_path, keep));
+        }
+    }
+
+    new_root
+}
+
+// Entries hidden by convention on Unix, whose names start with a dot.
+pub fn is_dotfile(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+// Files macOS leaves behind to store Finder settings (.DS_Store) and, on
+// filesystems without extended attributes, resource forks (._name).
+pub fn is_macos_metadata(name: &str) -> bool {
+    name == ".DS_Store" || name.starts_with("._")
+}
+
+// The paths of siblings whose names differ only by case, like README and
+// Readme. Only one of them can exist on the default filesystems of macOS and
+// Windows, so copying the tree there loses the others.
+pub fn case_conflicts(root: &TreeNode, path: &Path, conflicts: &mut HashSet<PathBuf>) {
+    let mut names: HashMap<String, &str> = HashMap::new();
+    for child in &root.children {
+        if let Some(other) = names.insert(child.val.to_lowercase(), &child.val) {
+            conflicts.insert(path.join(other));
+            conflicts.insert(path.join(&child.val));
+        }
+        case_conflicts(child, &path.join(&child.val), conflicts);
+    }
+}
+
+// Returns a copy of the tree with only the conflicting entries and the
+// directories leading to them.
+pub fn only_case_conflicts(root: &TreeNode, path: &Path, conflicts: &HashSet<PathBuf>) -> TreeNode {
+    let leading: HashSet<&Path> = conflicts.iter().flat_map(|c| c.ancestors()).collect();
+    prune_tree(root, path, &|path, _| leading.contains(path))
+}
+
+// Whether names in the directory are matched regardless of case, as on the
+// default filesystems of macOS and Windows. This is probed by looking the
+// directory up again with the case of its name flipped.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let (parent, name) = match (dir.parent(), dir.file_name()) {
+        (Some(parent), Some(name)) => (parent, name.to_string_lossy()),
+        _ => return false,
+    };
+
+    let flipped: String = name
+        .chars()
+        .map(|c| {
+            if c.is_uppercase() {
+                c.to_lowercase().next().unwrap_or(c)
+            } else {
+                c.to_uppercase().next().unwrap_or(c)
+            }
+        })
+        .collect();
+    if flipped == name {
+        return false;
+    }
+
+    match (
+        std::fs::metadata(dir),
+        std::fs::metadata(parent.join(flipped)),
+    ) {
+        (Ok(original), Ok(flipped)) => same_file(&original, &flipped),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &std::fs::Metadata, _b: &std::fs::Metadata) -> bool {
+    true
+}
+
+pub fn filter_tree(root: &TreeNode, filter: &Regex) -> TreeNode {
     let mut new_root = TreeNode {
         color: root.color,
         val: root.val.clone(),
@@ -72,7 +307,7 @@ pub fn filter_tree(root: &TreeNode, filter: &str) -> TreeNode {
 
     for child in &root.children {
         let node = filter_tree(child, filter);
-        if node.children.len() != 0 || node.val.contains(filter) {
+        if !node.children.is_empty() || filter.is_match(&node.val) {
             new_root.children.push(node);
         }
     }
@@ -80,29 +315,228 @@ pub fn filter_tree(root: &TreeNode, filter: &str) -> TreeNode {
     new_root
 }
 
-pub fn term_setup() -> Terminal<CrosstermBackend<std::io::Stdout>> {
-    enable_raw_mode().unwrap();
+// Whether the terminal can show the TUI. Pipes and dumb terminals, such as
+// editor shells and some IDE consoles, don't understand the escapes for raw
+// mode and the alternate screen. Keys are read from the terminal even when
+// stdin is a pipe, which crossterm does through /dev/tty on Unix and the
+// console input on Windows.
+pub fn is_capable_terminal() -> bool {
+    let dumb = matches!(std::env::var("TERM").as_deref(), Ok("dumb"));
+    io::stdout().is_terminal() && has_keyboard() && !dumb
+}
+
+#[cfg(unix)]
+fn has_keyboard() -> bool {
+    io::stdin().is_terminal() || std::fs::File::open("/dev/tty").is_ok()
+}
+
+#[cfg(not(unix))]
+fn has_keyboard() -> bool {
+    true
+}
+
+// Without mouse capture the TUI draws on the main screen, where terminals
+// leave the mouse to selecting and copying text.
+pub fn term_setup(mouse: bool) -> io::Result<Terminal<CrosstermBackend<std::io::Stdout>>> {
+    enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture).unwrap();
+    if let Err(e) = execute!(stdout, EnableBracketedPaste) {
+        let _ = disable_raw_mode();
+        return Err(e);
+    }
+    if mouse {
+        if let Err(e) = execute!(stdout, EnterAlternateScreen, EnableMouseCapture) {
+            let _ = disable_raw_mode();
+            return Err(e);
+        }
+    }
+    push_title(&mut stdout);
+    // Terminals implementing the kitty keyboard protocol then report keys
+    // that are otherwise sent as the same bytes, like Esc and the start of
+    // Alt+key or Tab and Ctrl+I, as distinct keys with all their modifiers.
+    // Other terminals ignore the request. Crossterm can't send it on
+    // Windows, where the console reports keys unambiguously anyway.
+    let _ = execute!(
+        stdout,
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+    );
     let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend).unwrap();
+    let mut terminal = Terminal::new(backend)?;
 
-    terminal.clear().unwrap();
+    terminal.clear()?;
 
-    terminal
+    Ok(terminal)
 }
 
-pub fn term_teardown(terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>) {
-    disable_raw_mode().unwrap();
-    execute!(
+pub fn term_teardown(terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>, mouse: bool) {
+    let _ = execute!(
         terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )
-    .unwrap();
+        PopKeyboardEnhancementFlags,
+        DisableBracketedPaste
+    );
+    disable_raw_mode().unwrap();
+    if mouse {
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )
+        .unwrap();
+    } else {
+        terminal.clear().unwrap();
+    }
+    pop_title(terminal.backend_mut());
     terminal.show_cursor().unwrap();
 }
 
+// Colors, the title stack and the clipboard are set with escape sequences
+// written directly rather than through crossterm. Windows consoles only
+// understand them once virtual terminal processing is turned on, which
+// crossterm does when asked whether they support it. Consoles too old for
+// it are left without them instead of showing them as text.
+#[cfg(windows)]
+pub fn ansi_supported() -> bool {
+    crossterm::ansi_support::supports_ansi()
+}
+
+#[cfg(not(windows))]
+pub fn ansi_supported() -> bool {
+    true
+}
+
+// Saves the current window title on the terminal's title stack (XTWINOPS)
+// so that it can be restored on exit.
+fn push_title(w: &mut impl Write) {
+    if !ansi_supported() {
+        return;
+    }
+    write!(w, "\x1b[22;0t").unwrap();
+    w.flush().unwrap();
+}
+
+fn pop_title(w: &mut impl Write) {
+    if !ansi_supported() {
+        return;
+    }
+    write!(w, "\x1b[23;0t").unwrap();
+    w.flush().unwrap();
+}
+
+pub fn window_title(root: &str, pattern: &str) -> String {
+    if pattern.is_empty() {
+        format!("tree-rs — {}", root)
+    } else {
+        format!("tree-rs — {} — {}", root, pattern)
+    }
+}
+
+pub fn set_title(title: &str) {
+    execute!(io::stdout(), SetTitle(title)).unwrap();
+}
+
+// On Windows settings go to %APPDATA%\tree-rs.
+#[cfg(windows)]
+pub fn config_dir() -> Option<PathBuf> {
+    let appdata = std::env::var_os("APPDATA").filter(|dir| !dir.is_empty())?;
+    Some(PathBuf::from(appdata).join("tree-rs"))
+}
+
+#[cfg(not(windows))]
+pub fn config_dir() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("tree-rs"))
+}
+
+pub fn parse_color(name: &str) -> Option<Color> {
+    let color = match name.trim().to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "white" => Color::White,
+        index => Color::Indexed(index.parse().ok()?),
+    };
+    Some(color)
+}
+
+pub fn ring_bell() {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x07").unwrap();
+    stdout.flush().unwrap();
+}
+
+// Programs that put what they read on the clipboard, tried in turn.
+const CLIPBOARD_COMMANDS: [&[&str]; 5] = [
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+    &["clip.exe"],
+];
+
+// Copies the text to the system clipboard with the first clipboard program
+// that works. Over ssh that is the clipboard of the wrong machine, so the
+// text is also sent with the OSC 52 escape sequence, which the terminal puts
+// on its own clipboard. Terminals that don't support it ignore it.
+pub fn copy_to_clipboard(text: &str) {
+    for command in CLIPBOARD_COMMANDS {
+        if copy_with(command, text) {
+            break;
+        }
+    }
+
+    if !ansi_supported() {
+        return;
+    }
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes())).unwrap();
+    stdout.flush().unwrap();
+}
+
+fn copy_with(command: &[&str], text: &str) -> bool {
+    let child = process::Command::new(command[0])
+        .args(&command[1..])
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait().is_ok_and(|status| status.success())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
 pub fn get_tree_count(root: &TreeNode, node_type: NodeType) -> usize {
     let mut count = 0;
     for child in &root.children {
@@ -113,3 +547,61 @@ pub fn get_tree_count(root: &TreeNode, node_type: NodeType) -> usize {
     }
     count
 }
+
+// Like filter_tree, with `matched` deciding by the path of an entry.
+pub fn filter_tree_by_path(
+    root: &TreeNode,
+    path: &Path,
+    matched: &dyn Fn(&Path) -> bool,
+) -> TreeNode {
+    let mut new_root = TreeNode {
+        color: root.color,
+        val: root.val.clone(),
+        children: Vec::new(),
+        node_type: root.node_type,
+    };
+
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        let node = filter_tree_by_path(child, &child_path, matched);
+        if !node.children.is_empty() || matched(&child_path) {
+            new_root.children.push(node);
+        }
+    }
+
+    new_root
+}
+
+pub fn count_path_matches(root: &TreeNode, path: &Path, matched: &dyn Fn(&Path) -> bool) -> usize {
+    let mut count = 0;
+    for child in &root.children {
+        let child_path = path.join(&child.val);
+        if matched(&child_path) {
+            count += 1;
+        }
+        count += count_path_matches(child, &child_path, matched);
+    }
+    count
+}
+
+// A path below the root with / between its components on every system, as
+// globs are written.
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+pub fn count_matches(root: &TreeNode, filter: &Regex) -> usize {
+    let mut count = 0;
+    for child in &root.children {
+        if filter.is_match(&child.val) {
+            count += 1;
+        }
+        count += count_matches(child, filter);
+    }
+    count
+}
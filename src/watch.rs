@@ -0,0 +1,55 @@
+// The settings kept in files, the script, the plugin directory and the
+// config file, are watched while the TUI runs so that edits apply without
+// restarting and scanning the tree again. The files are checked by their
+// modification time every CHECK_INTERVAL, which costs a few stats.
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Watch {
+    // Each file with its modification time, None while it doesn't exist.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    checked_at: Instant,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Watch {
+    pub fn new(paths: Vec<PathBuf>) -> Watch {
+        Watch {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let time = modified(&path);
+                    (path, time)
+                })
+                .collect(),
+            checked_at: Instant::now(),
+        }
+    }
+
+    // Whether any of the files was changed, created or removed since the
+    // last call. Only checks once per interval.
+    pub fn changed(&mut self) -> bool {
+        if self.checked_at.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.checked_at = Instant::now();
+
+        let mut changed = false;
+        for (path, time) in &mut self.files {
+            let current = modified(path);
+            if current != *time {
+                *time = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
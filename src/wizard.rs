@@ -0,0 +1,166 @@
+// On the first start in a terminal, before there is a config file, a short
+// wizard asks for the settings people most often change and writes them to
+// the config file. Skipping it with Esc writes the defaults, so it is only
+// shown once. Deleting the config file brings it back.
+
+use crate::{
+    config::write_config,
+    i18n::{tr, trf},
+    util::{display_path, term_setup, term_teardown},
+    SORT_NAMES,
+};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use std::io;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+// The columns offered to sort by, those holding a number.
+const NUMERIC_COLUMNS: [&str; 4] = ["size", "disk", "loc", "inodes"];
+
+struct Question {
+    // The option the answer is written to.
+    option: &'static str,
+    // The key of the question in the UI strings.
+    text: &'static str,
+    // The values to choose from, the default first.
+    choices: Vec<&'static str>,
+    chosen: usize,
+}
+
+impl Question {
+    fn new(option: &'static str, text: &'static str, choices: Vec<&'static str>) -> Question {
+        Question {
+            option,
+            text,
+            choices,
+            chosen: 0,
+        }
+    }
+}
+
+fn questions() -> Vec<Question> {
+    vec![
+        Question::new(
+            "palette",
+            "setup_palette",
+            vec!["default", "high-contrast", "colorblind"],
+        ),
+        Question::new("depth-colors", "setup_depth_colors", vec!["false", "true"]),
+        Question::new("icons", "setup_icons", vec!["false", "true"]),
+        Question::new(
+            "sort",
+            "setup_sort",
+            SORT_NAMES
+                .iter()
+                .chain(NUMERIC_COLUMNS.iter())
+                .copied()
+                .collect(),
+        ),
+    ]
+}
+
+// Flags are offered as on and off rather than true and false.
+fn choice_label(value: &str) -> String {
+    match value {
+        "true" => tr("on"),
+        "false" => tr("off"),
+        value => value.to_string(),
+    }
+}
+
+// Runs the wizard and writes the config file. Returns a message telling
+// where the settings went.
+pub fn run_wizard(mouse: bool) -> io::Result<String> {
+    let mut terminal = term_setup(mouse)?;
+    let mut questions = questions();
+    let mut current = 0;
+
+    let skipped = loop {
+        terminal.draw(|f| draw(f, &questions, current))?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        let question = &mut questions[current];
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => question.chosen = question.chosen.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                question.chosen = (question.chosen + 1).min(question.choices.len() - 1);
+            }
+            KeyCode::Left | KeyCode::Backspace => current = current.saturating_sub(1),
+            KeyCode::Enter | KeyCode::Right => {
+                current += 1;
+                if current == questions.len() {
+                    break false;
+                }
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break true,
+            KeyCode::Esc => break true,
+            _ => {}
+        }
+    };
+
+    term_teardown(&mut terminal, mouse);
+
+    if skipped {
+        questions
+            .iter_mut()
+            .for_each(|question| question.chosen = 0);
+    }
+    let settings: Vec<(&str, &str)> = questions
+        .iter()
+        .map(|question| (question.option, question.choices[question.chosen]))
+        .collect();
+    Ok(match write_config(&settings) {
+        Ok(path) => trf("setup_saved", &[&display_path(&path)]),
+        Err(e) => trf("setup_failed", &[&e]),
+    })
+}
+
+fn draw(f: &mut Frame<impl Backend>, questions: &[Question], current: usize) {
+    let question = &questions[current];
+    let reversed = Style::default().add_modifier(Modifier::REVERSED);
+
+    let mut text = vec![
+        Spans::from(tr("setup_intro")),
+        Spans::from(""),
+        Spans::from(Span::styled(
+            format!("{}/{}  {}", current + 1, questions.len(), tr(question.text)),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+    ];
+    for (i, choice) in question.choices.iter().enumerate() {
+        let label = format!("  {}", choice_label(choice));
+        text.push(if i == question.chosen {
+            Spans::from(Span::styled(label, reversed))
+        } else {
+            Spans::from(label)
+        });
+    }
+    text.push(Spans::from(""));
+    text.push(Spans::from(tr("setup_hints")));
+
+    // The introduction wraps over several rows.
+    let area = f.size();
+    let width = area.width.min(72);
+    let intro_rows = tr("setup_intro").chars().count() as u16 / width.saturating_sub(2).max(1);
+    let height = (text.len() as u16 + intro_rows + 2).min(area.height);
+    let size = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let widget = Paragraph::new(text)
+        .block(Block::default().title(tr("setup")).borders(Borders::ALL))
+        .wrap(Wrap { trim: false });
+    f.render_widget(widget, size);
+}
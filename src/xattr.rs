@@ -0,0 +1,157 @@
+// Extended attributes and ACLs of a file, read without following symlinks.
+// They are only supported on Linux and macOS; elsewhere every file has none.
+
+use std::path::Path;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    fn c_path(path: &Path) -> Option<CString> {
+        CString::new(path.as_os_str().as_bytes()).ok()
+    }
+
+    // Calls a function returning the size of a buffer twice: once to get the
+    // size and once to fill the buffer.
+    fn read_sized(call: impl Fn(*mut libc::c_void, usize) -> libc::ssize_t) -> Option<Vec<u8>> {
+        let size = call(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return None;
+        }
+
+        let mut buffer = vec![0u8; size as usize];
+        let size = call(buffer.as_mut_ptr() as *mut libc::c_void, buffer.len());
+        if size < 0 {
+            return None;
+        }
+        buffer.truncate(size as usize);
+        Some(buffer)
+    }
+
+    pub fn get(path: &Path, name: &str) -> Option<Vec<u8>> {
+        let path = c_path(path)?;
+        let name = CString::new(name).ok()?;
+
+        read_sized(|buffer, size| unsafe {
+            #[cfg(target_os = "linux")]
+            return libc::lgetxattr(path.as_ptr(), name.as_ptr(), buffer, size);
+            #[cfg(target_os = "macos")]
+            return libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buffer,
+                size,
+                0,
+                libc::XATTR_NOFOLLOW,
+            );
+        })
+    }
+
+    pub fn list(path: &Path) -> Vec<String> {
+        let path = match c_path(path) {
+            Some(path) => path,
+            None => return Vec::new(),
+        };
+
+        let names = read_sized(|buffer, size| unsafe {
+            #[cfg(target_os = "linux")]
+            return libc::llistxattr(path.as_ptr(), buffer as *mut libc::c_char, size);
+            #[cfg(target_os = "macos")]
+            return libc::listxattr(
+                path.as_ptr(),
+                buffer as *mut libc::c_char,
+                size,
+                libc::XATTR_NOFOLLOW,
+            );
+        });
+
+        // The names are NUL terminated and packed one after the other.
+        names
+            .unwrap_or_default()
+            .split(|&byte| byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .collect()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod sys {
+    use std::path::Path;
+
+    pub fn get(_path: &Path, _name: &str) -> Option<Vec<u8>> {
+        None
+    }
+
+    pub fn list(_path: &Path) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+pub fn get(path: &Path, name: &str) -> Option<Vec<u8>> {
+    sys::get(path, name)
+}
+
+// Attributes other than those holding the ACL and the SELinux context,
+// which are shown on their own.
+pub fn list(path: &Path) -> Vec<String> {
+    sys::list(path)
+        .into_iter()
+        .filter(|name| !ACL_ATTRIBUTES.contains(&name.as_str()) && name != SELINUX_ATTRIBUTE)
+        .collect()
+}
+
+const SELINUX_ATTRIBUTE: &str = "security.selinux";
+
+// Linux stores POSIX ACLs in these attributes.
+const ACL_ATTRIBUTES: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+// The entries of the file's access ACL, e.g. "user:1000:rw-", or None if it
+// has no ACL beyond its permission bits. Only Linux ACLs are decoded.
+pub fn acl(path: &Path) -> Option<Vec<String>> {
+    let value = get(path, ACL_ATTRIBUTES[0])?;
+
+    // A version header followed by entries of a 16-bit tag, 16-bit
+    // permissions and a 32-bit user or group id, all little endian.
+    let mut entries = Vec::new();
+    for entry in value.get(4..)?.chunks_exact(8) {
+        let tag = u16::from_le_bytes([entry[0], entry[1]]);
+        let perm = u16::from_le_bytes([entry[2], entry[3]]);
+        let id = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
+
+        let qualifier = match tag {
+            0x01 => "user::".to_string(),
+            0x02 => format!("user:{}:", id),
+            0x04 => "group::".to_string(),
+            0x08 => format!("group:{}:", id),
+            0x10 => "mask::".to_string(),
+            0x20 => "other::".to_string(),
+            _ => continue,
+        };
+        let rwx: String = [(4, 'r'), (2, 'w'), (1, 'x')]
+            .iter()
+            .map(|&(bit, c)| if perm & bit != 0 { c } else { '-' })
+            .collect();
+        entries.push(format!("{}{}", qualifier, rwx));
+    }
+    Some(entries)
+}
+
+// The SELinux security context, e.g. "system_u:object_r:etc_t:s0".
+pub fn selinux_context(path: &Path) -> Option<String> {
+    let value = get(path, SELINUX_ATTRIBUTE)?;
+    let context = String::from_utf8_lossy(&value);
+    Some(context.trim_end_matches('\0').to_string()).filter(|context| !context.is_empty())
+}
+
+// Files downloaded on macOS are tagged with a quarantine attribute of the
+// form "flags;timestamp;agent;id". The agent is the application that
+// downloaded the file.
+pub fn quarantine(path: &Path) -> Option<String> {
+    let value = get(path, "com.apple.quarantine")?;
+    let value = String::from_utf8_lossy(&value).to_string();
+    match value.split(';').nth(2) {
+        Some(agent) if !agent.is_empty() => Some(agent.to_string()),
+        _ => Some(value),
+    }
+}